@@ -0,0 +1,121 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    player::{interpolate_player_camera, Player, PlayerLanded},
+};
+
+/// How stiff the spring pulling the camera back up after a landing dip is.
+const LANDING_DIP_STIFFNESS: f32 = 150.0;
+
+/// How quickly the sprint FOV kick and head bob ease in and out, per second.
+const EASE_RATE: f32 = 8.0;
+
+/// Per-camera state for the effects that make the first-person camera feel
+/// less rigidly attached to the player's capsule.
+#[derive(Component, Default)]
+pub struct CameraEffects {
+    bob_phase: f32,
+    bob_weight: f32,
+    dip: f32,
+    dip_velocity: f32,
+    fov_kick: f32,
+}
+
+fn ease_towards(current: f32, target: f32, dt: f32) -> f32 {
+    current + (target - current) * (1.0 - (-EASE_RATE * dt).exp())
+}
+
+fn update_camera_effects(
+    time: Res<Time>,
+    mut landed_events: EventReader<PlayerLanded>,
+    player_query: Query<&Player>,
+    mut camera_query: Query<(&Parent, &mut CameraEffects, &mut Transform, &mut Projection)>,
+    config: Res<Config>,
+) {
+    let dt = time.delta_seconds();
+    let landing_velocity = landed_events
+        .iter()
+        .map(|landed| landed.velocity)
+        .fold(0.0, f32::max);
+    for (parent, mut effects, mut transform, mut projection) in &mut camera_query {
+        let Ok(player) = player_query.get(parent.get()) else {
+            warn!("Parent of camera has no player!");
+            continue;
+        };
+        let speed = player.walk_velocity().length();
+        let is_walking = player.is_grounded() && speed > 0.0;
+
+        // Head bob.
+        let bob_target = if config.camera_head_bob_enabled && is_walking {
+            1.0
+        } else {
+            0.0
+        };
+        effects.bob_weight = ease_towards(effects.bob_weight, bob_target, dt);
+        if is_walking {
+            effects.bob_phase = (effects.bob_phase
+                + dt * config.camera_head_bob_frequency * TAU * speed / config.player_speed)
+                % TAU;
+        }
+        let bob_amplitude = config.camera_head_bob_amplitude * effects.bob_weight;
+        // We bob up and down twice for every side-to-side sway, once per footstep.
+        let bob = Vec3::new(
+            0.5 * bob_amplitude * effects.bob_phase.sin(),
+            bob_amplitude * (2.0 * effects.bob_phase).sin().abs(),
+            0.0,
+        );
+
+        // Landing dip, which is a critically damped spring.
+        if config.camera_landing_dip_enabled && landing_velocity > 0.0 {
+            effects.dip_velocity -= (landing_velocity * config.camera_landing_dip_amount)
+                .min(config.camera_landing_dip_max);
+        }
+        let damping = 2.0 * LANDING_DIP_STIFFNESS.sqrt();
+        let dip_acceleration =
+            -LANDING_DIP_STIFFNESS * effects.dip - damping * effects.dip_velocity;
+        effects.dip_velocity += dip_acceleration * dt;
+        effects.dip += effects.dip_velocity * dt;
+        if !config.camera_landing_dip_enabled {
+            effects.dip = 0.0;
+            effects.dip_velocity = 0.0;
+        }
+
+        // The bob is rotated by our yaw so that it always sways side to side
+        // relative to the direction we're looking.
+        let yaw = Quat::from_axis_angle(Vec3::Y, transform.rotation.to_euler(EulerRot::YXZ).0);
+        transform.translation = Vec3::new(0.0, config.player_camera_height + effects.dip, 0.0)
+            + yaw * bob
+            + player.camera_offset();
+
+        // Field of view, including the sprint kick.
+        let fov_kick_target = if config.camera_sprint_fov_kick_enabled && player.is_sprinting() {
+            config.camera_sprint_fov_kick
+        } else {
+            0.0
+        };
+        effects.fov_kick = ease_towards(effects.fov_kick, fov_kick_target, dt);
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            let fov = (config.camera_fov + effects.fov_kick).to_radians();
+            if perspective.fov != fov {
+                perspective.fov = fov;
+            }
+        }
+    }
+}
+
+pub struct CameraEffectsPlugin;
+
+impl Plugin for CameraEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_camera_effects
+                .after(interpolate_player_camera)
+                .run_if(in_state(AppState::InGame)),
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use bevy::prelude::*;
+
+use crate::{app_state::AppState, config::Config, health::Dying, player::Player};
+
+/// The kinds of item the player can pick up, each placed in Blender with an
+/// object whose name ends in the kind's suffix, e.g. `Gold-key`.
+#[derive(
+    serde::Deserialize, serde::Serialize, Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CollectibleKind {
+    Coin,
+    Key,
+    Relic,
+}
+
+impl CollectibleKind {
+    const ALL: [CollectibleKind; 3] = [
+        CollectibleKind::Coin,
+        CollectibleKind::Key,
+        CollectibleKind::Relic,
+    ];
+
+    fn suffix(self) -> &'static str {
+        match self {
+            CollectibleKind::Coin => "-coin",
+            CollectibleKind::Key => "-key",
+            CollectibleKind::Relic => "-relic",
+        }
+    }
+
+    /// What to call more than one of them, e.g. in objectives.
+    pub fn plural(self) -> &'static str {
+        match self {
+            CollectibleKind::Coin => "coins",
+            CollectibleKind::Key => "keys",
+            CollectibleKind::Relic => "relics",
+        }
+    }
+}
+
+/// An item that's picked up when the player walks up to it, after which it's
+/// hidden.
+#[derive(Component)]
+pub struct Collectible {
+    pub kind: CollectibleKind,
+    /// Its name without the kind's suffix, e.g. `Gold` for `Gold-key`.
+    pub name: String,
+    collected: bool,
+}
+
+impl Collectible {
+    pub fn is_collected(&self) -> bool {
+        self.collected
+    }
+
+    /// Picks up or puts back the item, without sending `Collected`, e.g. when
+    /// loading a save.
+    pub fn set_collected(&mut self, collected: bool) {
+        self.collected = collected;
+    }
+}
+
+/// Sent when the player picks up an item.
+#[derive(Event)]
+pub struct Collected {
+    pub collectible: Entity,
+    pub kind: CollectibleKind,
+    pub name: String,
+}
+
+/// The kind of collectible an object is, and its name without the kind's
+/// suffix, if its name ends in one. Blender's `.001`-style tails, which it adds
+/// to keep copies' names unique, are ignored.
+fn parse_collectible_name(name: &str) -> Option<(CollectibleKind, &str)> {
+    let name = match name.rsplit_once('.') {
+        Some((base, tail)) if !tail.is_empty() && tail.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => name,
+    };
+    CollectibleKind::ALL
+        .into_iter()
+        .find_map(|kind| Some((kind, name.strip_suffix(kind.suffix())?)))
+}
+
+/// Turns objects named after the kinds of collectible into items. Also picks
+/// up any added after the scene was set up.
+///
+/// Only nodes, which have their meshes as children, are considered, since the
+/// glTF loader names the meshes themselves after their mesh data, which may
+/// have the same name as the node.
+fn setup_collectibles(
+    mut commands: Commands,
+    query: Query<(Entity, &Name), (Added<Name>, With<Children>)>,
+) {
+    let mut count = 0;
+    for (entity, name) in &query {
+        let Some((kind, item_name)) = parse_collectible_name(name) else {
+            continue;
+        };
+        count += 1;
+        commands.entity(entity).insert(Collectible {
+            kind,
+            name: item_name.to_string(),
+            collected: false,
+        });
+    }
+    if count > 0 {
+        info!("Set up {} collectibles.", count);
+    }
+}
+
+fn spin_collectibles(
+    time: Res<Time>,
+    mut query: Query<&mut Transform, With<Collectible>>,
+    config: Res<Config>,
+) {
+    for mut transform in &mut query {
+        transform.rotate_y(config.collectible_spin_speed * time.delta_seconds());
+    }
+}
+
+fn pick_up_collectibles(
+    player_query: Query<&Transform, (With<Player>, Without<Dying>)>,
+    mut collectibles: Query<(Entity, &GlobalTransform, &mut Collectible)>,
+    mut collected_events: EventWriter<Collected>,
+    config: Res<Config>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    // The player's transform is at the center of the bottom of their capsule.
+    let capsule_bottom = player.translation;
+    let capsule_top = capsule_bottom + Vec3::Y * config.player_capsule_cylinder_height;
+    let reach = config.player_capsule_radius + config.collectible_pickup_distance;
+    for (entity, transform, mut collectible) in &mut collectibles {
+        if collectible.collected {
+            continue;
+        }
+        let position = transform.translation();
+        let nearest_on_capsule = Vec3::new(
+            capsule_bottom.x,
+            position.y.clamp(capsule_bottom.y, capsule_top.y),
+            capsule_bottom.z,
+        );
+        if position.distance(nearest_on_capsule) > reach {
+            continue;
+        }
+        info!("Picked up {}.", collectible.name);
+        collectible.collected = true;
+        collected_events.send(Collected {
+            collectible: entity,
+            kind: collectible.kind,
+            name: collectible.name.clone(),
+        });
+    }
+}
+
+fn hide_collected_items(mut query: Query<(&Collectible, &mut Visibility), Changed<Collectible>>) {
+    for (collectible, mut visibility) in &mut query {
+        *visibility = if collectible.collected {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
+
+/// Coins, keys and relics placed in the level for the player to pick up.
+pub struct CollectiblePlugin;
+
+impl Plugin for CollectiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Collected>()
+            .add_systems(OnEnter(AppState::SettingUpScene), setup_collectibles)
+            .add_systems(
+                Update,
+                (
+                    setup_collectibles,
+                    spin_collectibles,
+                    pick_up_collectibles,
+                    hide_collected_items,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
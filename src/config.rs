@@ -1,69 +1,454 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, reflect::Struct, utils::HashMap};
 use bevy_common_assets::json::JsonAssetPlugin;
 
-use crate::app_state::{AppState, AssetsLoading};
+use crate::{
+    app_state::{AppState, AssetsLoading},
+    console::{AddConsoleCommand, ConsoleCommand},
+    objectives::Objective,
+};
+
+const CONFIG_PATH: &str = "config.json";
+
+/// Changes how a material from the level glows.
+#[derive(serde::Deserialize, serde::Serialize, Reflect, Default, Clone)]
+pub struct EmissiveOverride {
+    /// Replaces the emissive color the material was exported with.
+    #[serde(default)]
+    pub color: Option<Color>,
+    /// Multiplies the emissive color. This can put it into the HDR space so
+    /// bevy can apply bloom to it, etc.
+    #[serde(default = "default_emissive_scale")]
+    pub scale: f32,
+}
+
+fn default_emissive_scale() -> f32 {
+    1.0
+}
+
+/// A key that can be rebound in the config, e.g. `"E"`.
+#[derive(serde::Deserialize, serde::Serialize, Reflect, Clone, Copy, PartialEq, Debug)]
+#[serde(transparent)]
+pub struct KeyBinding(pub KeyCode);
+
+impl Default for KeyBinding {
+    fn default() -> Self {
+        KeyBinding(KeyCode::Unlabeled)
+    }
+}
+
+/// The key's name as it should be shown to the player, e.g. `1` rather than
+/// `Key1`.
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self.0 {
+            KeyCode::Key1 => "1",
+            KeyCode::Key2 => "2",
+            KeyCode::Key3 => "3",
+            KeyCode::Key4 => "4",
+            KeyCode::Key5 => "5",
+            KeyCode::Key6 => "6",
+            KeyCode::Key7 => "7",
+            KeyCode::Key8 => "8",
+            KeyCode::Key9 => "9",
+            KeyCode::Key0 => "0",
+            KeyCode::Escape => "Esc",
+            KeyCode::Back => "Backspace",
+            KeyCode::Return => "Enter",
+            KeyCode::Up => "Up Arrow",
+            KeyCode::Down => "Down Arrow",
+            KeyCode::Left => "Left Arrow",
+            KeyCode::Right => "Right Arrow",
+            KeyCode::PageUp => "Page Up",
+            KeyCode::PageDown => "Page Down",
+            KeyCode::ShiftLeft => "Left Shift",
+            KeyCode::ShiftRight => "Right Shift",
+            KeyCode::ControlLeft => "Left Ctrl",
+            KeyCode::ControlRight => "Right Ctrl",
+            KeyCode::AltLeft => "Left Alt",
+            KeyCode::AltRight => "Right Alt",
+            KeyCode::Grave => "`",
+            KeyCode::Minus => "-",
+            KeyCode::Equals => "=",
+            KeyCode::BracketLeft => "[",
+            KeyCode::BracketRight => "]",
+            KeyCode::Backslash => "\\",
+            KeyCode::Semicolon => ";",
+            KeyCode::Apostrophe => "'",
+            KeyCode::Comma => ",",
+            KeyCode::Period => ".",
+            KeyCode::Slash => "/",
+            // The rest, like letters and function keys, are already named the
+            // way they're labeled.
+            key => return write!(f, "{:?}", key),
+        };
+        f.write_str(name)
+    }
+}
 
 #[derive(
-    serde::Deserialize, bevy::reflect::TypeUuid, bevy::reflect::TypePath, Resource, Default, Clone,
+    serde::Deserialize, serde::Serialize, bevy::reflect::TypeUuid, Reflect, Resource, Default, Clone,
 )]
+#[reflect(Resource)]
 #[uuid = "83187ffe-c216-4626-803f-e2a96e016323"]
 pub struct Config {
     /// Player speed in meters per second.
     pub player_speed: f32,
+    /// The player's speed is multiplied by this while sprinting.
+    pub player_sprint_multiplier: f32,
     /// The distance of the camera from the bottom of the player's capsule.
     pub player_camera_height: f32,
     /// The radius of the player's capsule.
     pub player_capsule_radius: f32,
     /// The height of the cylindrical part of the player's capsule.
     pub player_capsule_cylinder_height: f32,
+    /// The maximum height of a step (e.g. a stair) that the player can
+    /// walk up without jumping, in meters.
+    pub player_autostep_max_height: f32,
+    /// The minimum width of free space that must be on top of a step
+    /// for the player to automatically climb it, in meters.
+    pub player_autostep_min_width: f32,
+    /// The steepest slope the player can walk up, in degrees.
+    pub player_max_slope_climb_angle: f32,
+    /// The minimum slope, in degrees, at which the player will start
+    /// sliding down.
+    pub player_min_slope_slide_angle: f32,
+    /// If the ground is within this distance below the player, in meters,
+    /// they will be snapped down to it. This prevents the player from
+    /// bouncing when walking down slopes and stairs.
+    pub player_snap_to_ground_distance: f32,
     pub mouse_sensitivity: f32,
-    /// Multiply the colors of all emissive materials by this amount.
-    /// This will put the colors into the HDR space so bevy can apply
-    /// bloom to it, etc.
-    pub emissive_scale: f32,
+    /// The key for using whatever the player is looking at.
+    pub use_key: KeyBinding,
+    /// How far away the player can use things from, in meters.
+    pub interaction_max_distance: f32,
+    /// Whether to smooth out mouse look.
+    pub mouse_smoothing_enabled: bool,
+    /// How long, in seconds, mouse look takes to catch up with the mouse
+    /// when smoothing is enabled.
+    pub mouse_smoothing: f32,
+    /// The camera's vertical field of view, in degrees.
+    pub camera_fov: f32,
+    /// Whether the camera bobs up and down while the player walks.
+    pub camera_head_bob_enabled: bool,
+    /// How far the camera bobs while walking, in meters.
+    pub camera_head_bob_amplitude: f32,
+    /// How many times per second the camera sways from side to side while
+    /// walking at normal speed.
+    pub camera_head_bob_frequency: f32,
+    /// Whether the camera dips down when the player lands.
+    pub camera_landing_dip_enabled: bool,
+    /// How fast the camera dips, in meters per second, for every meter per
+    /// second the player lands at.
+    pub camera_landing_dip_amount: f32,
+    /// The fastest the camera will dip when landing, in meters per second.
+    pub camera_landing_dip_max: f32,
+    /// Whether the field of view widens while sprinting.
+    pub camera_sprint_fov_kick_enabled: bool,
+    /// How much the field of view widens while sprinting, in degrees.
+    pub camera_sprint_fov_kick: f32,
+    /// Overrides for the emissive colors of the level's materials, keyed by
+    /// their names in Blender.
+    pub emissive_materials: HashMap<String, EmissiveOverride>,
     /// Gravity in meters per second squared.
     pub gravity: f32,
     /// Jump velocity in meters per second.
     pub jump_velocity: f32,
+    /// The speed at which the player climbs ladders and other climbable
+    /// surfaces, in meters per second.
+    pub player_climb_speed: f32,
     /// If the player's y-coordinate is below this value, they've fallen
     /// off the level and should be respawned.
     pub fall_off_level_y: f32,
-    /// If the player falls off the level, this will be their y-coordinate
-    /// when they're respawned.
+    /// Where the player starts, and where they respawn if they die before
+    /// reaching a checkpoint.
     pub spawn_position: Vec3,
-    /// Instructions shown at beginning of game.
+    /// The player's maximum (and starting) health.
+    pub player_max_health: f32,
+    /// Landing faster than this, in meters per second, hurts the player.
+    pub fall_damage_min_velocity: f32,
+    /// The damage the player takes for every meter per second they land
+    /// faster than `fall_damage_min_velocity`.
+    pub fall_damage_per_velocity: f32,
+    /// Damage per second dealt to the player while they're in a hazard.
+    pub hazard_damage_per_second: f32,
+    /// How long, in seconds, the screen takes to fade out when the player
+    /// dies, and to fade back in when they respawn.
+    pub death_fade_duration: f32,
+    /// Multiplies the volume of every sound, from 0 (silent) to 1 (full volume).
+    pub audio_master_volume: f32,
+    /// The volume of the player's footsteps, from 0 to 1.
+    pub audio_footsteps_volume: f32,
+    /// The volume of bodies hitting things, from 0 to 1.
+    pub audio_impacts_volume: f32,
+    /// The volume of the player's actions, like force pushing, from 0 to 1.
+    pub audio_effects_volume: f32,
+    /// The volume of looping sounds in the level, like torches, from 0 to 1.
+    pub audio_ambience_volume: f32,
+    /// How far the player walks between footsteps, in meters.
+    pub footstep_stride: f32,
+    /// Rigid bodies make a sound when they're hit with at least this impulse,
+    /// in newton-seconds.
+    pub impact_sound_min_impulse: f32,
+    /// Impacts with at least this impulse make sounds at full volume.
+    pub impact_sound_max_impulse: f32,
+    /// Turns off effects that are expensive to render, like torch particles
+    /// and flickering.
+    pub low_quality: bool,
+    pub torch_flicker_enabled: bool,
+    /// How much torch lights' intensity varies, as a fraction of their intensity.
+    pub torch_flicker_amount: f32,
+    /// How far torch lights wander from where they were placed, in meters.
+    pub torch_flicker_distance: f32,
+    /// Roughly how many times a second torch lights change direction as they
+    /// flicker.
+    pub torch_flicker_speed: f32,
+    pub torch_particles_enabled: bool,
+    /// The number of particles in each torch's flame.
+    pub torch_flame_particles: u32,
+    pub torch_ember_particles: u32,
+    pub torch_smoke_particles: u32,
+    /// How fast something has to be moving to put out a torch it hits, in
+    /// meters per second.
+    pub torch_extinguish_min_speed: f32,
+    /// How many point lights can cast shadows at once. The lights closest to
+    /// the camera that light up something in view get them.
+    pub max_shadowed_lights: u32,
+    /// Used instead of `max_shadowed_lights` when `low_quality` is on.
+    pub low_quality_max_shadowed_lights: u32,
+    /// The width and height of each point light's shadow maps, in pixels.
+    pub shadow_map_size: u32,
+    /// Used instead of `shadow_map_size` when `low_quality` is on, in pixels.
+    pub low_quality_shadow_map_size: u32,
+    /// Point lights start to fade out at this distance from the camera, in
+    /// meters.
+    pub light_fade_start_distance: f32,
+    /// Point lights further than this from the camera are turned off.
+    pub light_fade_end_distance: f32,
+    /// Enemies' walking speed in meters per second.
+    pub enemy_speed: f32,
+    /// Enemies' speed when chasing the player, in meters per second.
+    pub enemy_chase_speed: f32,
+    pub enemy_capsule_radius: f32,
+    pub enemy_capsule_cylinder_height: f32,
+    pub enemy_max_health: f32,
+    /// How far away enemies can see the player from, in meters.
+    pub enemy_sight_distance: f32,
+    /// How far to either side of straight ahead enemies can see, in degrees.
+    pub enemy_sight_angle: f32,
+    /// How close enemies have to be to the player to hit them, in meters.
+    pub enemy_attack_range: f32,
+    pub enemy_attack_damage: f32,
+    /// The time between enemies' attacks, in seconds.
+    pub enemy_attack_interval: f32,
+    /// How long enemies wait at each waypoint, in seconds.
+    pub enemy_idle_duration: f32,
+    /// Force pushed bodies hurt enemies they hit faster than this, in meters
+    /// per second.
+    pub enemy_impact_min_speed: f32,
+    /// The damage done to an enemy for every meter per second a force pushed
+    /// body hits it faster than `enemy_impact_min_speed`.
+    pub enemy_impact_damage_per_speed: f32,
+    /// The width of the cells the level is divided into when generating its
+    /// navmesh, in meters. This should be less than the player's diameter.
+    pub navmesh_cell_size: f32,
+    /// How close the player has to get to an item to pick it up, in meters
+    /// from the surface of their capsule.
+    pub collectible_pickup_distance: f32,
+    /// How fast items waiting to be picked up spin, in radians per second.
+    pub collectible_spin_speed: f32,
+    /// How close the player has to be to a locked door's surface, in meters,
+    /// for it to open if they have its key, or to be told which key it needs.
+    pub door_unlock_distance: f32,
+    /// How long doors take to slide open, in seconds.
+    pub door_open_duration: f32,
+    /// What the player has to do to complete each level, by level name.
+    pub objectives: HashMap<String, Vec<Objective>>,
+    /// Instructions shown at beginning of game. `{use_key}` is replaced with
+    /// the name of the use key.
     pub instructions: String,
     pub player_force_push_max_distance: f32,
     pub player_force_push_velocity: f32,
+    /// How strongly the player pushes dynamic bodies they walk into. For every
+    /// second of contact, a body of `player_push_max_mass` is accelerated by the
+    /// player's speed multiplied by this amount.
+    pub player_push_strength: f32,
+    /// The mass, in kilograms, of the heaviest body the player pushes at full
+    /// `player_push_strength`. The push is the same for every body, so heavier
+    /// ones are harder to push and lighter ones easier, though they're never
+    /// pushed faster than the player is walking.
+    pub player_push_max_mass: f32,
+    /// The initial speed of the noclip fly camera available in debug mode, in
+    /// meters per second. It can be changed with the mouse wheel while flying.
+    pub debug_fly_camera_speed: f32,
     pub ambient_color: Color,
     pub ambient_brightness: f32,
     pub clear_color: Color,
 }
 
 fn load_config(asset_server: ResMut<AssetServer>, mut loading: ResMut<AssetsLoading>) {
-    let config: Handle<Config> = asset_server.load("config.json");
+    let config: Handle<Config> = asset_server.load(CONFIG_PATH);
     loading.0.push(config.clone_untyped());
 }
 
-fn apply_config(mut config: ResMut<Config>, loaded_config: Res<Assets<Config>>) {
+/// Values changed with the `set` console command, which are kept when the
+/// config is applied again, e.g. when a level is loaded.
+#[derive(Resource, Default)]
+struct ConfigOverrides(HashMap<String, Vec<String>>);
+
+impl ConfigOverrides {
+    fn apply(&self, config: &mut Config) {
+        for (name, values) in &self.0 {
+            let values: Vec<&str> = values.iter().map(String::as_str).collect();
+            let Some(field) = config.field_mut(name) else {
+                continue;
+            };
+            if let Err(error) = set_reflected_value(field, &values) {
+                warn!("Couldn't set {}: {}", name, error);
+            }
+        }
+    }
+}
+
+pub(crate) fn apply_config(
+    mut config: ResMut<Config>,
+    loaded_config: Res<Assets<Config>>,
+    overrides: Res<ConfigOverrides>,
+) {
     for (_, loaded) in loaded_config.iter() {
         info!("Loaded configuration.");
         // Technically this means we are storing two copies of the configuration,
         // but it's pretty small and much more convenient to access as a global
         // resource than via an asset handle.
         *config = loaded.clone();
+        overrides.apply(&mut config);
         return;
     }
     error!("No configuration found!");
 }
 
+fn apply_reloaded_config(
+    mut events: EventReader<AssetEvent<Config>>,
+    mut config: ResMut<Config>,
+    loaded_config: Res<Assets<Config>>,
+    overrides: Res<ConfigOverrides>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } = event {
+            if let Some(loaded) = loaded_config.get(handle) {
+                info!("Reloaded configuration.");
+                *config = loaded.clone();
+                overrides.apply(&mut config);
+            }
+        }
+    }
+}
+
+fn config_field_names(_world: &World) -> Vec<String> {
+    let config = Config::default();
+    (0..config.field_len())
+        .filter_map(|index| config.name_at(index))
+        .map(String::from)
+        .collect()
+}
+
+fn set_reflected_value(field: &mut dyn Reflect, values: &[&str]) -> Result<(), String> {
+    let parse_f32 = |value: &str| {
+        value
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid number '{}'.", value))
+    };
+    if let Some(field) = field.downcast_mut::<f32>() {
+        let [value] = values else {
+            return Err("Expected a number.".to_string());
+        };
+        *field = parse_f32(value)?;
+    } else if let Some(field) = field.downcast_mut::<u32>() {
+        let [value] = values else {
+            return Err("Expected a whole number.".to_string());
+        };
+        *field = value
+            .parse()
+            .map_err(|_| format!("Invalid whole number '{}'.", value))?;
+    } else if let Some(field) = field.downcast_mut::<bool>() {
+        let [value] = values else {
+            return Err("Expected true or false.".to_string());
+        };
+        *field = value
+            .parse()
+            .map_err(|_| format!("Invalid boolean '{}'.", value))?;
+    } else if let Some(field) = field.downcast_mut::<Vec3>() {
+        let [x, y, z] = values else {
+            return Err("Expected x, y and z values.".to_string());
+        };
+        *field = Vec3::new(parse_f32(x)?, parse_f32(y)?, parse_f32(z)?);
+    } else if let Some(field) = field.downcast_mut::<KeyBinding>() {
+        let [value] = values else {
+            return Err("Expected a key name.".to_string());
+        };
+        *field = serde_json::from_value(serde_json::Value::String(value.to_string()))
+            .map_err(|_| format!("Invalid key name '{}'.", value))?;
+    } else if let Some(field) = field.downcast_mut::<String>() {
+        *field = values.join(" ").replace("\\n", "\n");
+    } else {
+        return Err(format!("Can't set values of type {}.", field.type_name()));
+    }
+    Ok(())
+}
+
+fn set_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let [name, values @ ..] = args else {
+        return Err("Expected a config field name and value.".to_string());
+    };
+    let mut config = world.resource_mut::<Config>();
+    let Some(field) = config.field_mut(name) else {
+        return Err(format!("Unknown config field '{}'.", name));
+    };
+    set_reflected_value(field, values)?;
+    // Some fields, like the size of the player's capsule, are only used when
+    // setting up the scene, so changing them won't have an effect until then.
+    let message = format!("{} = {:?}", name, field);
+    world.resource_mut::<ConfigOverrides>().0.insert(
+        name.to_string(),
+        values.iter().map(|value| value.to_string()).collect(),
+    );
+    Ok(message)
+}
+
+fn reload_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let ["config"] = args else {
+        return Err("Expected 'config'.".to_string());
+    };
+    world.resource_mut::<ConfigOverrides>().0.clear();
+    world.resource::<AssetServer>().reload_asset(CONFIG_PATH);
+    Ok("Reloading configuration, without the values changed with 'set'...".to_string())
+}
+
 pub struct ConfigPlugin;
 
 impl Plugin for ConfigPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(JsonAssetPlugin::<Config>::new(&["json"]))
             .init_resource::<Config>()
+            .init_resource::<ConfigOverrides>()
+            .register_type::<Config>()
             .add_systems(Startup, load_config)
-            .add_systems(OnExit(AppState::LoadingAssets), apply_config);
+            .add_systems(OnExit(AppState::LoadingAssets), apply_config)
+            .add_systems(Update, apply_reloaded_config)
+            .add_console_command(
+                ConsoleCommand::new(
+                    "set",
+                    "set <field> <value> - change a config value",
+                    set_command,
+                )
+                .with_arguments(config_field_names),
+            )
+            .add_console_command(
+                ConsoleCommand::new(
+                    "reload",
+                    "reload config - reload config.json, discarding values changed with set",
+                    reload_command,
+                )
+                .with_arguments(|_| vec!["config".to_string()]),
+            );
     }
 }
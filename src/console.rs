@@ -0,0 +1,342 @@
+use bevy::{input::InputSystem, prelude::*, utils::HashMap, window::ReceivedCharacter};
+
+/// Runs a console command with the given arguments, returning the text to
+/// print in the console, or an error message.
+pub type ConsoleCommandFn = fn(&mut World, &[&str]) -> Result<String, String>;
+
+const TOGGLE_KEY: KeyCode = KeyCode::F1;
+
+const MAX_LOG_LINES: usize = 200;
+
+const VISIBLE_LOG_LINES: usize = 14;
+
+pub struct ConsoleCommand {
+    name: &'static str,
+    usage: &'static str,
+    run: ConsoleCommandFn,
+    /// Returns the values that arguments of the command can be tab-completed to.
+    arguments: fn(&World) -> Vec<String>,
+}
+
+impl ConsoleCommand {
+    pub fn new(name: &'static str, usage: &'static str, run: ConsoleCommandFn) -> Self {
+        ConsoleCommand {
+            name,
+            usage,
+            run,
+            arguments: |_| Vec::new(),
+        }
+    }
+
+    pub fn with_arguments(mut self, arguments: fn(&World) -> Vec<String>) -> Self {
+        self.arguments = arguments;
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(HashMap<&'static str, ConsoleCommand>);
+
+/// Lets plugins register their own console commands.
+pub trait AddConsoleCommand {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world
+            .resource_mut::<ConsoleCommands>()
+            .0
+            .insert(command.name, command);
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+struct ConsoleState {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+    history: Vec<String>,
+    /// When browsing history, the index of the history entry being shown.
+    history_index: Option<usize>,
+    /// Lines that have been entered but not yet run.
+    pending: Vec<String>,
+    /// Whether tab was pressed, so the input should be completed.
+    completion_requested: bool,
+}
+
+impl ConsoleState {
+    fn print(&mut self, text: &str) {
+        self.log.extend(text.lines().map(String::from));
+        let excess = self.log.len().saturating_sub(MAX_LOG_LINES);
+        self.log.drain(..excess);
+    }
+}
+
+#[derive(Component)]
+struct ConsoleRoot;
+
+#[derive(Component)]
+struct ConsoleLogText;
+
+#[derive(Component)]
+struct ConsoleInputText;
+
+fn setup_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+        font_size: 18.0,
+        color: Color::WHITE,
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    width: Val::Percent(100.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                z_index: ZIndex::Global(i32::MAX - 1),
+                ..default()
+            },
+            ConsoleRoot,
+            Name::new("Console"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                ConsoleLogText,
+            ));
+            parent.spawn((
+                TextBundle::from_section("", text_style.clone()).with_style(Style {
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                }),
+                ConsoleInputText,
+            ));
+        });
+}
+
+fn toggle_console(keys: Res<Input<KeyCode>>, mut state: ResMut<ConsoleState>) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        state.open = !state.open;
+    }
+}
+
+fn is_console_open(state: Res<ConsoleState>) -> bool {
+    state.open
+}
+
+fn console_input(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut state: ResMut<ConsoleState>,
+) {
+    for event in characters.iter() {
+        if !event.char.is_control() {
+            state.input.push(event.char);
+            state.history_index = None;
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        state.input.pop();
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut state.input);
+        if !line.trim().is_empty() {
+            if state.history.last() != Some(&line) {
+                state.history.push(line.clone());
+            }
+            state.pending.push(line);
+        }
+        state.history_index = None;
+    }
+    if keys.just_pressed(KeyCode::Up) && !state.history.is_empty() {
+        let index = match state.history_index {
+            Some(index) => index.saturating_sub(1),
+            None => state.history.len() - 1,
+        };
+        state.input = state.history[index].clone();
+        state.history_index = Some(index);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        if let Some(index) = state.history_index {
+            if index + 1 < state.history.len() {
+                state.input = state.history[index + 1].clone();
+                state.history_index = Some(index + 1);
+            } else {
+                state.input.clear();
+                state.history_index = None;
+            }
+        }
+    }
+    if keys.just_pressed(KeyCode::Tab) {
+        state.completion_requested = true;
+    }
+
+    // Don't let anything else respond to what we're typing, e.g. by moving the
+    // player or toggling debug features.
+    keys.reset_all();
+    buttons.reset_all();
+}
+
+/// Completes the input when tab is pressed. This needs the whole world, since
+/// commands' arguments can depend on anything, e.g. the assets on disk.
+fn complete_input(world: &mut World) {
+    if !world.resource::<ConsoleState>().completion_requested {
+        return;
+    }
+    world.resource_mut::<ConsoleState>().completion_requested = false;
+    let input = world.resource::<ConsoleState>().input.clone();
+    let words: Vec<&str> = input.split(' ').collect();
+    let Some((partial, previous)) = words.split_last() else {
+        return;
+    };
+    let commands = world.resource::<ConsoleCommands>();
+    let mut candidates: Vec<String> = if previous.is_empty() {
+        commands.0.keys().map(|name| name.to_string()).collect()
+    } else if let Some(command) = commands.0.get(previous[0]) {
+        (command.arguments)(world)
+    } else {
+        vec![]
+    };
+    let mut state = world.resource_mut::<ConsoleState>();
+    candidates.retain(|candidate| candidate.starts_with(partial));
+    candidates.sort();
+    let Some(first) = candidates.first() else {
+        return;
+    };
+    // Complete as much as all the candidates have in common.
+    let mut common = first.clone();
+    for candidate in &candidates[1..] {
+        while !candidate.starts_with(&common) {
+            common.pop();
+        }
+    }
+    let mut completed = previous.join(" ");
+    if !completed.is_empty() {
+        completed.push(' ');
+    }
+    completed.push_str(&common);
+    if candidates.len() == 1 {
+        completed.push(' ');
+    } else {
+        let options = candidates.join("  ");
+        state.print(&options);
+    }
+    state.input = completed;
+}
+
+/// Runs the console command with the given name, as though it had been typed
+/// into the console, e.g. by a test.
+pub fn run_console_command(world: &mut World, name: &str, args: &[&str]) -> Result<String, String> {
+    let run = world
+        .resource::<ConsoleCommands>()
+        .0
+        .get(name)
+        .map(|command| command.run)
+        .ok_or_else(|| format!("Unknown command '{}'. Type 'help' for a list.", name))?;
+    run(world, args)
+}
+
+fn run_console_commands(world: &mut World) {
+    if world.resource::<ConsoleState>().pending.is_empty() {
+        return;
+    }
+    let lines = std::mem::take(&mut world.resource_mut::<ConsoleState>().pending);
+    for line in lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, args)) = words.split_first() else {
+            continue;
+        };
+        world
+            .resource_mut::<ConsoleState>()
+            .print(&format!("> {}", line));
+        let output = run_console_command(world, name, args)
+            .unwrap_or_else(|error| format!("Error: {}", error));
+        info!("Console: {} => {}", line, output);
+        world.resource_mut::<ConsoleState>().print(&output);
+    }
+}
+
+fn update_console_ui(
+    state: Res<ConsoleState>,
+    mut root_query: Query<&mut Style, With<ConsoleRoot>>,
+    mut log_query: Query<&mut Text, (With<ConsoleLogText>, Without<ConsoleInputText>)>,
+    mut input_query: Query<&mut Text, (With<ConsoleInputText>, Without<ConsoleLogText>)>,
+) {
+    if !state.is_changed() {
+        return;
+    }
+    for mut style in &mut root_query {
+        style.display = if state.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+    for mut text in &mut log_query {
+        let start = state.log.len().saturating_sub(VISIBLE_LOG_LINES);
+        text.sections[0].value = state.log[start..].join("\n");
+    }
+    for mut text in &mut input_query {
+        text.sections[0].value = format!("> {}_", state.input);
+    }
+}
+
+fn help_command(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let mut usages: Vec<&str> = world
+        .resource::<ConsoleCommands>()
+        .0
+        .values()
+        .map(|command| command.usage)
+        .collect();
+    usages.sort();
+    Ok(usages.join("\n"))
+}
+
+fn clear_command(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    world.resource_mut::<ConsoleState>().log.clear();
+    Ok(String::new())
+}
+
+/// A drop-down developer console, toggled with `F1`, for running the commands
+/// that plugins register. It's only added to the game in debug mode.
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>()
+            .add_console_command(ConsoleCommand::new(
+                "help",
+                "help - list all commands",
+                help_command,
+            ))
+            .add_console_command(ConsoleCommand::new(
+                "clear",
+                "clear - clear the console",
+                clear_command,
+            ))
+            .add_systems(Startup, setup_console)
+            .add_systems(
+                PreUpdate,
+                console_input.after(InputSystem).run_if(is_console_open),
+            )
+            .add_systems(
+                PreUpdate,
+                toggle_console.after(InputSystem).before(console_input),
+            )
+            .add_systems(
+                Update,
+                (run_console_commands, complete_input, update_console_ui).chain(),
+            );
+    }
+}
@@ -22,6 +22,130 @@ fn toggle_grab_cursor(
     }
 }
 
+/// State for the noclip fly camera, which only exists while it's active.
+#[cfg(feature = "debug_mode")]
+#[derive(Resource)]
+struct FlyCamera {
+    camera: Entity,
+    player: Entity,
+    /// The camera's transform relative to the player when we started flying.
+    player_camera_transform: Transform,
+    /// Speed in meters per second.
+    speed: f32,
+}
+
+/// Pressing `N` detaches the camera from the player so it can fly through walls.
+/// Pressing `N` again returns it to the player, while `Shift+N` instead teleports
+/// the player to wherever the camera is.
+#[cfg(feature = "debug_mode")]
+fn toggle_fly_camera(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    fly_camera: Option<Res<FlyCamera>>,
+    camera_query: Query<(Entity, &Parent, &Transform), With<Camera3d>>,
+    mut transforms: Query<&mut Transform, Without<Camera3d>>,
+    mut players: Query<&mut crate::player::Player>,
+    global_transforms: Query<&GlobalTransform>,
+    config: Res<crate::config::Config>,
+) {
+    use bevy::transform::commands::BuildChildrenTransformExt;
+
+    if !keys.just_pressed(KeyCode::N) {
+        return;
+    }
+
+    let Some(fly_camera) = fly_camera else {
+        for (camera, parent, transform) in &camera_query {
+            if players.contains(parent.get()) {
+                info!("Entering fly camera mode.");
+                commands.entity(camera).remove_parent_in_place();
+                commands.insert_resource(FlyCamera {
+                    camera,
+                    player: parent.get(),
+                    player_camera_transform: *transform,
+                    speed: config.debug_fly_camera_speed,
+                });
+            }
+        }
+        return;
+    };
+
+    let teleport = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let mut camera_transform = fly_camera.player_camera_transform;
+    if teleport {
+        let Ok(global_transform) = global_transforms.get(fly_camera.camera) else {
+            warn!("Fly camera has no global transform!");
+            return;
+        };
+        let Ok(mut player_transform) = transforms.get_mut(fly_camera.player) else {
+            warn!("Player has no transform!");
+            return;
+        };
+        let position = global_transform.translation();
+        info!("Teleporting player to {}.", position);
+        player_transform.translation = position - camera_transform.translation;
+        camera_transform.rotation = global_transform.to_scale_rotation_translation().1;
+        if let Ok(mut player) = players.get_mut(fly_camera.player) {
+            *player = default();
+        }
+    }
+    info!("Leaving fly camera mode.");
+    commands
+        .entity(fly_camera.camera)
+        .insert(camera_transform)
+        .set_parent(fly_camera.player);
+    commands.remove_resource::<FlyCamera>();
+}
+
+#[cfg(feature = "debug_mode")]
+fn fly_camera_movement(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut wheel_events: EventReader<bevy::input::mouse::MouseWheel>,
+    mut fly_camera: ResMut<FlyCamera>,
+    mut camera_query: Query<&mut Transform, With<Camera3d>>,
+) {
+    for event in wheel_events.iter() {
+        fly_camera.speed = (fly_camera.speed * 1.1_f32.powf(event.y)).clamp(0.1, 1000.0);
+    }
+    let Ok(mut transform) = camera_query.get_mut(fly_camera.camera) else {
+        warn!("Fly camera no longer exists!");
+        return;
+    };
+    let mut velocity = Vec3::ZERO;
+    let forward = transform.forward();
+    let right = transform.right();
+    for key in keys.get_pressed() {
+        match key {
+            KeyCode::W => velocity += forward,
+            KeyCode::S => velocity -= forward,
+            KeyCode::A => velocity -= right,
+            KeyCode::D => velocity += right,
+            KeyCode::Space | KeyCode::E => velocity += Vec3::Y,
+            KeyCode::ControlLeft | KeyCode::C | KeyCode::Q => velocity -= Vec3::Y,
+            _ => {}
+        }
+    }
+    let mut speed = fly_camera.speed;
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        speed *= 4.0;
+    }
+    transform.translation += velocity.normalize_or_zero() * speed * time.delta_seconds();
+}
+
+#[cfg(feature = "debug_mode")]
+fn despawn_fly_camera(mut commands: Commands, fly_camera: Option<Res<FlyCamera>>) {
+    if let Some(fly_camera) = fly_camera {
+        commands.entity(fly_camera.camera).despawn_recursive();
+        commands.remove_resource::<FlyCamera>();
+    }
+}
+
+#[cfg(feature = "debug_mode")]
+fn is_fly_camera_active(fly_camera: Option<Res<FlyCamera>>) -> bool {
+    fly_camera.is_some()
+}
+
 pub fn is_in_debug_mode(primary_window: Query<&Window, With<PrimaryWindow>>) -> bool {
     if let Ok(window) = primary_window.get_single() {
         window.cursor.visible
@@ -38,7 +162,21 @@ impl Plugin for DebugModePlugin {
         {
             #[cfg(feature = "debug_mode")]
             {
-                app.add_systems(Update, toggle_grab_cursor);
+                app.add_systems(Update, toggle_grab_cursor).add_systems(
+                    Update,
+                    (
+                        toggle_fly_camera,
+                        fly_camera_movement
+                            .after(toggle_fly_camera)
+                            .run_if(is_fly_camera_active)
+                            .run_if(not(is_in_debug_mode)),
+                    )
+                        .run_if(in_state(crate::app_state::AppState::InGame)),
+                );
+                app.add_systems(
+                    OnExit(crate::app_state::AppState::InGame),
+                    despawn_fly_camera,
+                );
                 let inspector = bevy_inspector_egui::quick::WorldInspectorPlugin::new();
                 app.add_plugins(inspector.run_if(is_in_debug_mode));
             }
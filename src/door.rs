@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    health::Dying,
+    instructions::{spawn_centered_hud_text, Fonts},
+    inventory::Inventory,
+    player::Player,
+    time_scale::FIXED_TIMESTEP_SECONDS,
+};
+
+/// A door that's opened by the key with the same name, e.g. `Gold-door` is
+/// opened by `Gold-key`. It slides up out of the way when the player comes up
+/// to it with the key.
+#[derive(Component)]
+pub struct Door {
+    /// The name of the key that opens it.
+    pub key: String,
+    /// How far the door rises to open, which is its height.
+    height: f32,
+    open: bool,
+    /// How far the door has risen so far.
+    raised: f32,
+    /// Whether to move it to where it should be straight away, rather than
+    /// sliding it there.
+    snap: bool,
+}
+
+impl Door {
+    pub fn new(key: &str, height: f32) -> Self {
+        Door {
+            key: key.to_string(),
+            height,
+            open: false,
+            raised: 0.0,
+            snap: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens or closes the door straight away, e.g. when loading a save.
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.snap = true;
+    }
+
+    /// How far a point is from the door's collider, as if the door were closed,
+    /// so that wide doors and those modeled off-center are treated the same.
+    fn distance_when_closed(
+        &self,
+        entity: Entity,
+        point: Vec3,
+        rapier_context: &RapierContext,
+    ) -> Option<f32> {
+        // Rather than lowering the door, raise the point by as much.
+        let point = point + Vec3::Y * self.raised;
+        let is_door = |other| other == entity;
+        let filter = QueryFilter::new().predicate(&is_door);
+        rapier_context
+            .project_point(point, true, filter)
+            .map(|(_, projection)| projection.point.distance(point))
+    }
+}
+
+/// Tells the player which key a locked door needs when they come up to it.
+#[derive(Component)]
+struct DoorHint;
+
+#[derive(Component)]
+struct DoorHintText;
+
+fn open_unlocked_doors(
+    player_query: Query<&Transform, (With<Player>, Without<Dying>)>,
+    mut doors: Query<(Entity, &mut Door)>,
+    inventory: Res<Inventory>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+) {
+    let Ok(player) = player_query.get_single() else {
+        return;
+    };
+    for (entity, mut door) in &mut doors {
+        if door.open || !inventory.has_key(&door.key) {
+            continue;
+        }
+        let distance = door.distance_when_closed(entity, player.translation, &rapier_context);
+        if distance.is_some_and(|distance| distance <= config.door_unlock_distance) {
+            info!("Unlocked the {} door.", door.key);
+            door.open = true;
+        }
+    }
+}
+
+/// Runs in `FixedUpdate` before the physics step, so doors move along with the
+/// simulation and it sees where they are straight away.
+fn move_doors(mut doors: Query<(&mut Door, &mut Transform)>, config: Res<Config>) {
+    for (mut door, mut transform) in &mut doors {
+        let target = if door.open { door.height } else { 0.0 };
+        if door.raised == target {
+            door.snap = false;
+            continue;
+        }
+        let speed = door.height / config.door_open_duration.max(f32::EPSILON);
+        let step = speed * FIXED_TIMESTEP_SECONDS as f32;
+        let raised = if std::mem::take(&mut door.snap) {
+            target
+        } else if door.raised < target {
+            (door.raised + step).min(target)
+        } else {
+            (door.raised - step).max(target)
+        };
+        transform.translation.y += raised - door.raised;
+        door.raised = raised;
+    }
+}
+
+fn setup_door_hint(mut commands: Commands, fonts: Res<Fonts>) {
+    spawn_centered_hud_text(
+        &mut commands,
+        &fonts,
+        Style {
+            // Above the inventory strip.
+            bottom: Val::Px(60.0),
+            ..default()
+        },
+        (DoorHint, Name::new("DoorHint")),
+        DoorHintText,
+    );
+}
+
+fn update_door_hint(
+    player_query: Query<&Transform, (With<Player>, Without<Dying>)>,
+    doors: Query<(Entity, &Door)>,
+    inventory: Res<Inventory>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+    mut hint_query: Query<&mut Text, With<DoorHintText>>,
+) {
+    let locked_door = player_query.get_single().ok().and_then(|player| {
+        doors
+            .iter()
+            .filter(|(_, door)| !door.open && !inventory.has_key(&door.key))
+            .filter_map(|(entity, door)| {
+                let distance =
+                    door.distance_when_closed(entity, player.translation, &rapier_context)?;
+                Some((door, distance))
+            })
+            .filter(|(_, distance)| *distance <= config.door_unlock_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(door, _)| door)
+    });
+    let hint = match locked_door {
+        Some(door) => format!("This door is locked. It needs the {} key.", door.key),
+        None => String::new(),
+    };
+    for mut text in &mut hint_query {
+        if text.sections[0].value != hint {
+            text.sections[0].value = hint.clone();
+        }
+    }
+}
+
+fn despawn_door_hint(mut commands: Commands, query: Query<Entity, With<DoorHint>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Locked doors, made from objects named `<key>-door` in Blender, which open
+/// for the player once they have the matching key.
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fonts>()
+            .add_systems(OnEnter(AppState::InGame), setup_door_hint)
+            .add_systems(OnExit(AppState::InGame), despawn_door_hint)
+            .add_systems(
+                FixedUpdate,
+                (open_unlocked_doors, move_doors)
+                    .chain()
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(Update, update_door_hint.run_if(in_state(AppState::InGame)));
+    }
+}
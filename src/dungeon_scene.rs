@@ -1,42 +1,235 @@
-use bevy::{app::AppExit, pbr::NotShadowCaster, prelude::*, render::primitives::Aabb};
+use std::path::Path;
+
+use bevy::{
+    app::AppExit, gltf::Gltf, pbr::NotShadowCaster, prelude::*, render::primitives::Aabb,
+    utils::HashMap,
+};
 use bevy_rapier3d::prelude::*;
 
 use crate::{
     app_state::{start_game, AppState, AssetsLoading},
     config::Config,
+    console::{AddConsoleCommand, ConsoleCommand},
+    door::Door,
+    health::{Checkpoint, Hazard},
+    objectives::{Exit, PressurePlate},
 };
 
-const GLTF_SCENE: &str = "dungeon.gltf#Scene0";
+const DEFAULT_LEVEL: &str = "dungeon";
+
+/// The name of the level to load, which is the name of its glTF file
+/// without the extension.
+#[derive(Resource)]
+pub struct CurrentLevel(pub String);
+
+/// The root of the currently loaded level's scene.
+#[derive(Component)]
+pub struct DungeonScene;
+
+/// The glTF file of the currently loaded level, which lets us find its
+/// materials by name.
+#[derive(Resource)]
+struct LevelGltf(Handle<Gltf>);
+
+/// The emissive colors that the level's materials were exported with, so that
+/// overrides can be applied again without compounding.
+#[derive(Resource, Default)]
+struct ImportedEmissives(HashMap<Handle<StandardMaterial>, Color>);
+
+/// A volume that the player can climb while overlapping it, e.g. a ladder.
+#[derive(Component)]
+pub struct Climbable;
 
 fn load_scene(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut loading: ResMut<AssetsLoading>,
+    level: Res<CurrentLevel>,
 ) {
-    info!("Loading scene...");
-    let scene = asset_server.load(GLTF_SCENE);
+    info!("Loading scene {}...", level.0);
+    let gltf: Handle<Gltf> = asset_server.load(format!("{}.gltf", level.0));
+    loading.0.push(gltf.clone_untyped());
+    commands.insert_resource(LevelGltf(gltf));
+    let scene = asset_server.load(format!("{}.gltf#Scene0", level.0));
     loading.0.push(scene.clone_untyped());
     commands
         .spawn(SceneBundle { scene, ..default() })
-        .insert(Name::new("DungeonScene"));
+        .insert((DungeonScene, Name::new("DungeonScene")));
+}
+
+fn unload_scene(
+    mut commands: Commands,
+    query: Query<(Entity, &Handle<Scene>), With<DungeonScene>>,
+    mut loading: ResMut<AssetsLoading>,
+) {
+    for (entity, scene) in &query {
+        info!("Unloading scene...");
+        loading.0.retain(|handle| handle.id() != scene.id());
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
-pub struct DungeonScenePlugin;
+/// Puts back the emissive colors the level's materials were exported with, and
+/// forgets them, since the materials may stay loaded for the next level.
+fn restore_imported_emissives(
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut imported: ResMut<ImportedEmissives>,
+) {
+    for (handle, emissive) in imported.0.drain() {
+        if let Some(material) = materials.get_mut(&handle) {
+            material.emissive = emissive;
+        }
+    }
+}
+
+/// The levels in the assets directory, which are its glTF files, and the
+/// current level, which may be elsewhere, e.g. in a subdirectory.
+fn level_names(world: &World) -> Vec<String> {
+    let asset_io = world.resource::<AssetServer>().asset_io();
+    // This is empty on the web, where directories can't be listed.
+    let mut levels: Vec<String> = asset_io
+        .read_directory(Path::new(""))
+        .map(|paths| {
+            paths
+                .filter(|path| path.extension() == Some("gltf".as_ref()))
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let current = &world.resource::<CurrentLevel>().0;
+    if !levels.contains(current) {
+        levels.push(current.clone());
+    }
+    levels
+}
+
+fn level_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let [name] = args else {
+        return Err("Expected a level name.".to_string());
+    };
+    if *world.resource::<State<AppState>>().get() != AppState::InGame {
+        return Err("A level is already being loaded.".to_string());
+    }
+    world.insert_resource(CurrentLevel(name.to_string()));
+    world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::LoadingAssets);
+    Ok(format!("Loading level {}...", name))
+}
+
+fn spawn_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let is_barrel = match args {
+        ["crate"] => false,
+        ["barrel"] => true,
+        _ => return Err("Expected 'crate' or 'barrel'.".to_string()),
+    };
+    // Rather than making a crate or barrel from scratch, we copy one from the scene.
+    let mut templates =
+        world.query_filtered::<(&Name, &Transform, &Collider, &Children), With<RigidBody>>();
+    let Some((name, transform, collider, children)) = templates
+        .iter(world)
+        .find(|(name, ..)| name.contains("-rigid") && name.contains("Barrel") == is_barrel)
+    else {
+        return Err(format!("No {} found in the scene to copy.", args[0]));
+    };
+    let name = Name::new(format!("{} (spawned)", name));
+    let mut transform = *transform;
+    let collider = collider.clone();
+    let meshes: Vec<PbrBundle> = children
+        .iter()
+        .filter_map(|&child| {
+            Some(PbrBundle {
+                mesh: world.get::<Handle<Mesh>>(child)?.clone(),
+                material: world.get::<Handle<StandardMaterial>>(child)?.clone(),
+                transform: *world.get::<Transform>(child)?,
+                ..default()
+            })
+        })
+        .collect();
+
+    let mut cameras = world.query_filtered::<&GlobalTransform, With<Camera3d>>();
+    let Some(camera) = cameras.iter(world).next() else {
+        return Err("No camera to spawn in front of.".to_string());
+    };
+    transform.translation = camera.translation() + camera.forward() * 2.0;
+
+    let mut scenes = world.query_filtered::<Entity, With<DungeonScene>>();
+    let Some(scene) = scenes.iter(world).next() else {
+        return Err("No scene to spawn into.".to_string());
+    };
+    let entity = world
+        .spawn((
+            SpatialBundle::from_transform(transform),
+            RigidBody::Dynamic,
+            collider,
+            Velocity::default(),
+            ReadMassProperties::default(),
+            name.clone(),
+        ))
+        .with_children(|parent| {
+            for mesh in meshes {
+                parent.spawn(mesh);
+            }
+        })
+        .id();
+    // Making it part of the scene means it will go away when the level is unloaded.
+    world.entity_mut(scene).add_child(entity);
+    Ok(format!("Spawned {} at {}.", name, transform.translation))
+}
+
+/// Loads a level exported from Blender and sets it up for the game, based on
+/// the naming conventions of its objects.
+pub struct DungeonScenePlugin {
+    /// The level to load first.
+    pub level: String,
+}
+
+impl Default for DungeonScenePlugin {
+    fn default() -> Self {
+        DungeonScenePlugin {
+            level: DEFAULT_LEVEL.to_string(),
+        }
+    }
+}
 
 impl Plugin for DungeonScenePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_scene)
+        app.insert_resource(CurrentLevel(self.level.clone()))
+            .add_systems(OnEnter(AppState::LoadingAssets), load_scene)
+            .add_systems(
+                OnExit(AppState::InGame),
+                (unload_scene, restore_imported_emissives),
+            )
+            .add_console_command(
+                ConsoleCommand::new(
+                    "spawn",
+                    "spawn crate|barrel - spawn an object in front of the camera",
+                    spawn_command,
+                )
+                .with_arguments(|_| vec!["crate".to_string(), "barrel".to_string()]),
+            )
+            .add_console_command(
+                ConsoleCommand::new("level", "level <name> - load a level", level_command)
+                    .with_arguments(level_names),
+            )
             .init_resource::<AssetsLoading>()
+            .init_resource::<ImportedEmissives>()
             .add_systems(
                 Update,
-                wait_for_scene_to_load.run_if(in_state(AppState::LoadingAssets)),
+                (
+                    wait_for_scene_to_load.run_if(in_state(AppState::LoadingAssets)),
+                    // So that overrides can be tweaked by hot reloading the config,
+                    // and are applied to the level's materials when it's re-exported.
+                    apply_emissive_overrides.run_if(in_state(AppState::InGame).and_then(
+                        resource_changed::<Config>().or_else(on_event::<AssetEvent<Gltf>>()),
+                    )),
+                ),
             )
             .add_systems(
                 OnEnter(AppState::SettingUpScene),
                 (
                     set_global_rendering_resources,
-                    fix_scene_emissive_materials,
-                    fix_scene_point_lights,
+                    apply_emissive_overrides,
                     fix_scene_torches,
                     fix_scene_physics,
                     start_game,
@@ -83,6 +276,8 @@ fn fix_scene_physics(
 ) {
     let mut colonly_count = 0;
     let mut rigid_count = 0;
+    let mut volume_count = 0;
+    let mut door_count = 0;
     info!("Iterating over {} meshes.", query.iter().count());
     for (entity, name, mut visibility, children) in &mut query {
         if name.contains("-colonly") {
@@ -178,36 +373,111 @@ fn fix_scene_physics(
             commands
                 .entity(entity)
                 .insert(RigidBody::Dynamic)
-                .insert(collider);
+                .insert(collider)
+                .insert(Velocity::default())
+                .insert(ReadMassProperties::default());
+        } else if name.contains("-climbable") {
+            volume_count += 1;
+            *visibility = Visibility::Hidden;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands
+                .entity(entity)
+                .insert((collider, Sensor, Climbable));
+        } else if name.contains("-hazard") {
+            volume_count += 1;
+            *visibility = Visibility::Hidden;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands.entity(entity).insert((collider, Sensor, Hazard));
+        } else if name.contains("-checkpoint") {
+            volume_count += 1;
+            *visibility = Visibility::Hidden;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands
+                .entity(entity)
+                .insert((collider, Sensor, Checkpoint));
+        } else if name.contains("-exit") {
+            volume_count += 1;
+            *visibility = Visibility::Hidden;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands.entity(entity).insert((collider, Sensor, Exit));
+        } else if name.contains("-plate") {
+            volume_count += 1;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands
+                .entity(entity)
+                .insert((collider, Sensor, PressurePlate));
+        } else if let Some((key, _)) = name.split_once("-door") {
+            door_count += 1;
+            let height = volume_aabb(name, children, &child_meshes_query, &meshes)
+                .half_extents
+                .y
+                * 2.0;
+            let collider = volume_collider(name, children, &child_meshes_query, &meshes);
+            commands.entity(entity).insert((
+                collider,
+                // So that it can be moved out of the way when it's opened.
+                RigidBody::KinematicPositionBased,
+                Door::new(key, height),
+            ));
         }
     }
 
     info!(
-        "Converted {} collision-only meshes and added {} rigid body colliders.",
-        colonly_count, rigid_count
+        "Converted {} collision-only meshes, added {} rigid body colliders, {} sensor volumes and {} doors.",
+        colonly_count, rigid_count, volume_count, door_count
     );
 }
 
+/// The box that encloses all the child meshes of a volume or door.
+fn volume_aabb(
+    name: &Name,
+    children: &Children,
+    child_meshes_query: &Query<(&Name, &Handle<Mesh>)>,
+    meshes: &Assets<Mesh>,
+) -> Aabb {
+    let mut aabb = Aabb::default();
+    for child in children.iter() {
+        let Ok((child_name, mesh_handle)) = child_meshes_query.get(*child) else {
+            warn!("volume object {} child has no mesh.", name);
+            continue;
+        };
+        let Some(mesh_aabb) = meshes.get(mesh_handle).and_then(|mesh| mesh.compute_aabb()) else {
+            warn!("volume object {} mesh {} has no AABB.", name, child_name);
+            continue;
+        };
+        aabb = union_aabb(&aabb, &mesh_aabb);
+    }
+    aabb
+}
+
+/// Creates a box collider that encloses all the child meshes of a sensor volume,
+/// e.g. a climbable, hazard, checkpoint, exit or pressure plate, or of a door.
+fn volume_collider(
+    name: &Name,
+    children: &Children,
+    child_meshes_query: &Query<(&Name, &Handle<Mesh>)>,
+    meshes: &Assets<Mesh>,
+) -> Collider {
+    let aabb = volume_aabb(name, children, child_meshes_query, meshes);
+    // Unlike our barrels and crates, volumes aren't necessarily centered
+    // around their origin, so offset the collider to match the mesh.
+    Collider::compound(vec![(
+        aabb.center.into(),
+        Quat::IDENTITY,
+        Collider::cuboid(
+            aabb.half_extents.x,
+            aabb.half_extents.y,
+            aabb.half_extents.z,
+        ),
+    )])
+}
+
 fn union_aabb(a: &Aabb, b: &Aabb) -> Aabb {
     let min = a.min().min(b.min());
     let max = a.max().max(b.max());
     Aabb::from_min_max(min.into(), max.into())
 }
 
-fn fix_scene_point_lights(mut query: Query<&mut PointLight>) {
-    let mut count = 0;
-    for mut light in &mut query {
-        if !light.shadows_enabled {
-            // Even though we've told the light to cast shadows in Blender, either Blender's glTF exporter doesn't
-            // export this, or bevy doesn't import it. Either way, we need to enable it manually.
-            count += 1;
-            light.shadows_enabled = true;
-        }
-    }
-
-    info!("Enabled shadows for {} point lights.", count);
-}
-
 fn fix_scene_torches(
     mut commands: Commands,
     query: Query<(Entity, &Name), Without<NotShadowCaster>>,
@@ -225,19 +495,52 @@ fn fix_scene_torches(
     info!("Disabled shadows for {} torches.", count);
 }
 
-fn fix_scene_emissive_materials(
+fn apply_emissive_overrides(
+    level: Res<LevelGltf>,
+    gltfs: Res<Assets<Gltf>>,
+    mut gltf_events: EventReader<AssetEvent<Gltf>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut imported: ResMut<ImportedEmissives>,
     config: Res<Config>,
 ) {
-    for (_handle, mat) in materials.iter_mut() {
-        let [r, g, b, _a] = mat.emissive.as_linear_rgba_f32();
-        let scale = config.emissive_scale;
-        if r > 0.0 || g > 0.0 || b > 0.0 {
-            info!(
-                "Scaling emissive {:?} by a factor of {}.",
-                mat.emissive, scale
-            );
-            mat.emissive = Color::rgb_linear(r * scale, g * scale, b * scale);
+    let Some(gltf) = gltfs.get(&level.0) else {
+        warn!("The level's glTF isn't loaded, so can't override its emissive materials.");
+        return;
+    };
+    // Hot reloading the level replaces its materials with the newly exported ones.
+    if gltf_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { handle } if *handle == level.0))
+    {
+        imported.0.clear();
+    }
+    for name in config.emissive_materials.keys() {
+        if !gltf.named_materials.contains_key(name) {
+            warn!("The level has no material named {} to override.", name);
+        }
+    }
+    // Only touching the level's own materials means we leave alone those of
+    // anything else, like particles.
+    for (name, handle) in &gltf.named_materials {
+        let Some(material) = materials.get(handle) else {
+            continue;
+        };
+        let imported_emissive = *imported
+            .0
+            .entry(handle.clone())
+            .or_insert(material.emissive);
+        let emissive = match config.emissive_materials.get(name) {
+            Some(emissive_override) => {
+                let color = emissive_override.color.unwrap_or(imported_emissive);
+                let [r, g, b, _a] = color.as_linear_rgba_f32();
+                let scale = emissive_override.scale;
+                Color::rgb_linear(r * scale, g * scale, b * scale)
+            }
+            None => imported_emissive,
+        };
+        if material.emissive != emissive {
+            info!("Setting emissive of {} to {:?}.", name, emissive);
+            materials.get_mut(handle).unwrap().emissive = emissive;
         }
     }
 }
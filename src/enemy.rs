@@ -0,0 +1,555 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    console::{AddConsoleCommand, ConsoleCommand},
+    health::{Dying, Health},
+    player::{Player, PlayerForcePush},
+    time_scale::FIXED_TIMESTEP_SECONDS,
+};
+
+/// How close an enemy has to get to somewhere it's walking to, e.g. a
+/// waypoint, to count as having arrived, in meters.
+const ARRIVAL_DISTANCE: f32 = 0.3;
+
+/// How long a body that the player force pushed can hurt enemies for, in
+/// seconds.
+const LAUNCH_DURATION: f32 = 2.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EnemyState {
+    /// Standing still, e.g. at a waypoint.
+    Idle,
+    /// Walking to the next waypoint.
+    Patrol,
+    /// Running towards the player, or where they were last seen.
+    Chase,
+    /// Close enough to the player to hit them.
+    Attack,
+}
+
+/// An inhabitant of the dungeon, spawned from an object named `<name>-enemy`
+/// in Blender. It patrols between the objects named `<name>-waypoint.*`, in
+/// order of their names.
+#[derive(Component)]
+pub struct Enemy {
+    state: EnemyState,
+    /// How long the enemy has been in its current state, or since its last
+    /// attack.
+    state_time: f32,
+    waypoints: Vec<Vec3>,
+    next_waypoint: usize,
+    last_seen_player: Option<Vec3>,
+    /// The enemy's vertical velocity, from falling.
+    fall_velocity: f32,
+    grounded: bool,
+}
+
+impl Enemy {
+    fn new(waypoints: Vec<Vec3>) -> Self {
+        Enemy {
+            state: EnemyState::Idle,
+            state_time: 0.0,
+            waypoints,
+            next_waypoint: 0,
+            last_seen_player: None,
+            fall_velocity: 0.0,
+            grounded: false,
+        }
+    }
+
+    pub fn state(&self) -> EnemyState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: EnemyState) {
+        if self.state != state {
+            self.state = state;
+            self.state_time = 0.0;
+        }
+    }
+}
+
+/// The mesh that shows an enemy, which is a child of its capsule.
+#[derive(Component)]
+struct EnemyBody;
+
+/// A body that the player force pushed, which hurts enemies that it hits.
+#[derive(Component)]
+struct Launched {
+    timer: Timer,
+    /// How fast the body was moving before the latest physics step, in meters
+    /// per second. Collision events only come after the step, by which time
+    /// the collision has slowed it down.
+    speed: f32,
+}
+
+#[derive(Resource)]
+struct EnemyAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_enemy_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<Config>,
+) {
+    commands.insert_resource(EnemyAssets {
+        mesh: meshes.add(
+            shape::Capsule {
+                radius: config.enemy_capsule_radius,
+                depth: config.enemy_capsule_cylinder_height,
+                ..default()
+            }
+            .into(),
+        ),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.35, 0.05, 0.05),
+            perceptual_roughness: 0.8,
+            ..default()
+        }),
+    });
+}
+
+/// Spawns an enemy standing at `position`, facing along `rotation`, which will
+/// patrol between the given waypoints.
+pub fn spawn_enemy(
+    world: &mut World,
+    name: &str,
+    mut position: Vec3,
+    rotation: Quat,
+    waypoints: Vec<Vec3>,
+) -> Entity {
+    let config = world.resource::<Config>().clone();
+    let (mesh, material) = {
+        let assets = world.resource::<EnemyAssets>();
+        (assets.mesh.clone(), assets.material.clone())
+    };
+    // The bottom hemisphere of the capsule is centered on the enemy's origin,
+    // just like the player's.
+    position.y += config.enemy_capsule_radius;
+    world
+        .spawn((
+            RigidBody::KinematicPositionBased,
+            Collider::capsule(
+                Vec3::ZERO,
+                Vec3::new(0.0, config.enemy_capsule_cylinder_height, 0.0),
+                config.enemy_capsule_radius,
+            ),
+            // So that we know when something hits it.
+            ActiveEvents::COLLISION_EVENTS,
+            SpatialBundle::from_transform(
+                Transform::from_translation(position).with_rotation(rotation),
+            ),
+            KinematicCharacterController {
+                up: Vec3::Y,
+                autostep: Some(CharacterAutostep {
+                    max_height: CharacterLength::Absolute(config.player_autostep_max_height),
+                    min_width: CharacterLength::Absolute(config.player_autostep_min_width),
+                    include_dynamic_bodies: false,
+                }),
+                snap_to_ground: Some(CharacterLength::Absolute(
+                    config.player_snap_to_ground_distance,
+                )),
+                apply_impulse_to_dynamic_bodies: false,
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
+                ..default()
+            },
+            Enemy::new(waypoints),
+            Health::new(config.enemy_max_health),
+            Name::new(name.to_string()),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                PbrBundle {
+                    mesh,
+                    material,
+                    transform: Transform::from_xyz(
+                        0.0,
+                        config.enemy_capsule_cylinder_height / 2.0,
+                        0.0,
+                    ),
+                    ..default()
+                },
+                EnemyBody,
+            ));
+        })
+        .id()
+}
+
+/// Spawns an enemy at each enemy marker in the scene.
+fn spawn_scene_enemies(world: &mut World) {
+    let mut markers = vec![];
+    let mut waypoints = vec![];
+    let mut query = world.query::<(Entity, &Name, &GlobalTransform)>();
+    for (entity, name, transform) in query.iter(world) {
+        if let Some((prefix, _)) = name.split_once("-enemy") {
+            markers.push((entity, prefix.to_string(), *transform));
+        } else if let Some((prefix, _)) = name.split_once("-waypoint") {
+            waypoints.push((
+                entity,
+                prefix.to_string(),
+                name.to_string(),
+                transform.translation(),
+            ));
+        }
+    }
+    waypoints.sort_by(|(_, _, a, _), (_, _, b, _)| a.cmp(b));
+
+    // Markers and waypoints may have been given meshes in Blender to make them
+    // easier to place, but they shouldn't be seen in the game.
+    let marker_entities = markers.iter().map(|(entity, ..)| *entity);
+    let waypoint_entities = waypoints.iter().map(|(entity, ..)| *entity);
+    for entity in marker_entities.chain(waypoint_entities).collect::<Vec<_>>() {
+        if let Some(mut visibility) = world.get_mut::<Visibility>(entity) {
+            *visibility = Visibility::Hidden;
+        }
+    }
+
+    let count = markers.len();
+    for (_, prefix, transform) in markers {
+        let enemy_waypoints: Vec<Vec3> = waypoints
+            .iter()
+            .filter(|(_, waypoint_prefix, ..)| *waypoint_prefix == prefix)
+            .map(|(.., position)| *position)
+            .collect();
+        // Enemies only turn around the vertical axis.
+        let (_, rotation, position) = transform.to_scale_rotation_translation();
+        let (yaw, _, _) = rotation.to_euler(EulerRot::YXZ);
+        spawn_enemy(
+            world,
+            &format!("Enemy ({})", prefix),
+            position,
+            Quat::from_rotation_y(yaw),
+            enemy_waypoints,
+        );
+    }
+
+    info!("Spawned {} enemies.", count);
+}
+
+/// Whether the enemy can see the player's capsule from `eye`.
+fn can_see_player(
+    rapier_context: &RapierContext,
+    enemy: Entity,
+    eye: Vec3,
+    forward: Vec3,
+    player: Entity,
+    target: Vec3,
+    chasing: bool,
+    config: &Config,
+) -> bool {
+    let offset = target - eye;
+    let distance = offset.length();
+    if distance > config.enemy_sight_distance || distance == 0.0 {
+        return false;
+    }
+    // Enemies only notice what's in front of them, but once they're chasing
+    // the player, they keep track of them as they dodge around.
+    if !chasing && offset.angle_between(forward) > config.enemy_sight_angle.to_radians() {
+        return false;
+    }
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .exclude_rigid_body(enemy);
+    rapier_context
+        .cast_ray(eye, offset / distance, distance, true, filter)
+        .is_some_and(|(entity, _)| entity == player)
+}
+
+/// Decides what each enemy does, and moves it accordingly.
+fn update_enemy_ai(
+    mut enemy_query: Query<
+        (
+            Entity,
+            &mut Enemy,
+            &mut KinematicCharacterController,
+            &mut Transform,
+            Option<&Dying>,
+        ),
+        Without<Player>,
+    >,
+    mut player_query: Query<(Entity, &Transform, &mut Health, Option<&Dying>), With<Player>>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+) {
+    let dt = FIXED_TIMESTEP_SECONDS as f32;
+    let mut player = player_query.get_single_mut().ok();
+    for (entity, mut enemy, mut controller, mut transform, dying) in &mut enemy_query {
+        if enemy.grounded && enemy.fall_velocity < 0.0 {
+            enemy.fall_velocity = 0.0;
+        }
+        // Like the player, we apply gravity even when grounded so that the
+        // character controller keeps us on the floor.
+        enemy.fall_velocity -= config.gravity * dt;
+        let fall = Vec3::new(0.0, enemy.fall_velocity * dt, 0.0);
+        if dying.is_some() {
+            controller.translation = Some(fall);
+            continue;
+        }
+        enemy.state_time += dt;
+
+        let eye = transform.translation + Vec3::Y * config.enemy_capsule_cylinder_height;
+        let mut player_position = None;
+        if let Some((player_entity, player_transform, _, None)) = &player {
+            let target = player_transform.translation
+                + Vec3::Y * (config.player_capsule_cylinder_height / 2.0);
+            let chasing = matches!(enemy.state, EnemyState::Chase | EnemyState::Attack);
+            if can_see_player(
+                &rapier_context,
+                entity,
+                eye,
+                transform.forward(),
+                *player_entity,
+                target,
+                chasing,
+                &config,
+            ) {
+                player_position = Some(player_transform.translation);
+                enemy.last_seen_player = player_position;
+            }
+        }
+        let horizontal_distance = |to: Vec3| {
+            let offset = to - transform.translation;
+            Vec2::new(offset.x, offset.z).length()
+        };
+
+        // Work out what to do, and where to go.
+        if let Some(position) = player_position {
+            if horizontal_distance(position) <= config.enemy_attack_range {
+                enemy.set_state(EnemyState::Attack);
+            } else {
+                enemy.set_state(EnemyState::Chase);
+            }
+        } else if enemy.state == EnemyState::Attack {
+            enemy.set_state(EnemyState::Chase);
+        }
+        let mut destination = None;
+        let mut speed = config.enemy_speed;
+        match enemy.state {
+            EnemyState::Idle => {
+                if !enemy.waypoints.is_empty() && enemy.state_time >= config.enemy_idle_duration {
+                    enemy.set_state(EnemyState::Patrol);
+                }
+            }
+            EnemyState::Patrol => {
+                let waypoint = enemy.waypoints[enemy.next_waypoint];
+                if horizontal_distance(waypoint) <= ARRIVAL_DISTANCE {
+                    enemy.next_waypoint = (enemy.next_waypoint + 1) % enemy.waypoints.len();
+                    enemy.set_state(EnemyState::Idle);
+                } else {
+                    destination = Some(waypoint);
+                }
+            }
+            EnemyState::Chase => {
+                speed = config.enemy_chase_speed;
+                match enemy.last_seen_player {
+                    // Having lost sight of the player, we look where they were
+                    // last seen, then give up.
+                    Some(last_seen) if horizontal_distance(last_seen) > ARRIVAL_DISTANCE => {
+                        destination = Some(last_seen);
+                    }
+                    _ => {
+                        enemy.last_seen_player = None;
+                        enemy.set_state(EnemyState::Idle);
+                    }
+                }
+            }
+            EnemyState::Attack => {
+                if let Some(position) = player_position {
+                    // Turn to face the player without moving.
+                    let offset = position - transform.translation;
+                    let facing = Vec3::new(offset.x, 0.0, offset.z);
+                    if facing != Vec3::ZERO {
+                        transform.look_to(facing, Vec3::Y);
+                    }
+                }
+                if enemy.state_time >= config.enemy_attack_interval {
+                    enemy.state_time = 0.0;
+                    if let Some((_, _, health, _)) = &mut player {
+                        info!(
+                            "An enemy hit the player for {}.",
+                            config.enemy_attack_damage
+                        );
+                        health.damage(config.enemy_attack_damage);
+                    }
+                }
+            }
+        }
+
+        let mut walk = Vec3::ZERO;
+        if let Some(destination) = destination {
+            let offset = destination - transform.translation;
+            let direction = Vec3::new(offset.x, 0.0, offset.z).normalize_or_zero();
+            if direction != Vec3::ZERO {
+                transform.look_to(direction, Vec3::Y);
+            }
+            walk = direction * speed * dt;
+        }
+        controller.translation = Some(walk + fall);
+    }
+}
+
+fn update_enemies_after_physics(
+    mut query: Query<(
+        &mut Enemy,
+        &mut Health,
+        &Transform,
+        &KinematicCharacterControllerOutput,
+    )>,
+    config: Res<Config>,
+) {
+    for (mut enemy, mut health, transform, output) in &mut query {
+        enemy.grounded = output.grounded;
+        if transform.translation.y < config.fall_off_level_y {
+            health.current = 0.0;
+        }
+    }
+}
+
+fn launch_force_pushed_bodies(
+    mut commands: Commands,
+    mut force_pushes: EventReader<PlayerForcePush>,
+) {
+    for force_push in force_pushes.iter() {
+        if let Some(target) = force_push.target {
+            commands.entity(target).insert(Launched {
+                timer: Timer::from_seconds(LAUNCH_DURATION, TimerMode::Once),
+                speed: 0.0,
+            });
+        }
+    }
+}
+
+fn update_launched_bodies(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Launched, &Velocity)>,
+) {
+    for (entity, mut launched, velocity) in &mut query {
+        launched.speed = velocity.linvel.length();
+        launched
+            .timer
+            .tick(std::time::Duration::from_secs_f64(FIXED_TIMESTEP_SECONDS));
+        if launched.timer.finished() {
+            commands.entity(entity).remove::<Launched>();
+        }
+    }
+}
+
+/// Hurts enemies hit by bodies that the player force pushed, more so the faster
+/// they were going.
+fn damage_enemies_hit_by_launched_bodies(
+    mut commands: Commands,
+    mut collision_events: EventReader<CollisionEvent>,
+    launched_query: Query<&Launched>,
+    mut enemy_query: Query<&mut Health, (With<Enemy>, Without<Dying>)>,
+    config: Res<Config>,
+) {
+    for event in collision_events.iter() {
+        let CollisionEvent::Started(a, b, _) = *event else {
+            continue;
+        };
+        let (body, enemy) = if launched_query.contains(a) {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let (Ok(launched), Ok(mut health)) = (launched_query.get(body), enemy_query.get_mut(enemy))
+        else {
+            continue;
+        };
+        let damage = (launched.speed - config.enemy_impact_min_speed).max(0.0)
+            * config.enemy_impact_damage_per_speed;
+        if damage > 0.0 {
+            info!(
+                "An enemy was hit at {} m/s, taking {} damage.",
+                launched.speed, damage
+            );
+            health.damage(damage);
+        }
+        // Each push only gets one hit.
+        commands.entity(body).remove::<Launched>();
+    }
+}
+
+/// Sinks dying enemies into the floor, then gets rid of them.
+fn update_dying_enemies(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &Dying, &Children), With<Enemy>>,
+    mut body_query: Query<&mut Transform, With<EnemyBody>>,
+    config: Res<Config>,
+) {
+    for (entity, dying, children) in &enemy_query {
+        if dying.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let height = config.enemy_capsule_cylinder_height + 2.0 * config.enemy_capsule_radius;
+        for &child in children {
+            if let Ok(mut transform) = body_query.get_mut(child) {
+                transform.translation.y =
+                    config.enemy_capsule_cylinder_height / 2.0 - dying.0.percent() * height;
+            }
+        }
+    }
+}
+
+fn despawn_enemies(mut commands: Commands, query: Query<Entity, With<Enemy>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn enemy_command(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    if *world.resource::<State<AppState>>().get() != AppState::InGame {
+        return Err("Can't spawn enemies while a level is loading.".to_string());
+    }
+    let mut cameras = world.query_filtered::<&GlobalTransform, With<Camera3d>>();
+    let Some(camera) = cameras.iter(world).next() else {
+        return Err("No camera to spawn in front of.".to_string());
+    };
+    let forward = camera.forward();
+    let forward = Vec3::new(forward.x, 0.0, forward.z).normalize_or_zero();
+    let mut position = camera.translation() + forward * 3.0;
+    position.y -= world.resource::<Config>().player_camera_height;
+    // Face the camera.
+    let rotation = Transform::default().looking_to(-forward, Vec3::Y).rotation;
+    spawn_enemy(world, "Enemy", position, rotation, vec![]);
+    Ok(format!("Spawned an enemy at {}.", position))
+}
+
+/// Enemies that patrol, chase the player when they see them and attack them up
+/// close. They can be hurt by throwing things at them with the force push.
+pub struct EnemyPlugin;
+
+impl Plugin for EnemyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            OnEnter(AppState::SettingUpScene),
+            (setup_enemy_assets, apply_deferred, spawn_scene_enemies).chain(),
+        )
+        .add_systems(OnExit(AppState::InGame), despawn_enemies)
+        .add_console_command(ConsoleCommand::new(
+            "enemy",
+            "enemy - spawn an enemy in front of the camera",
+            enemy_command,
+        ))
+        .add_systems(
+            FixedUpdate,
+            (
+                (update_launched_bodies, update_enemy_ai).before(PhysicsSet::SyncBackend),
+                damage_enemies_hit_by_launched_bodies.after(PhysicsSet::StepSimulation),
+                update_enemies_after_physics.after(PhysicsSet::Writeback),
+            )
+                .run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(
+            Update,
+            (launch_force_pushed_bodies, update_dying_enemies).run_if(in_state(AppState::InGame)),
+        );
+    }
+}
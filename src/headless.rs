@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use bevy::{
+    app::{AppExit, PluginGroupBuilder},
+    ecs::event::ManualEventReader,
+    prelude::*,
+    render::{settings::WgpuSettings, RenderPlugin},
+    time::TimeUpdateStrategy,
+    utils::Instant,
+    winit::WinitPlugin,
+};
+
+use crate::{app_state::AppState, player::Player};
+
+/// How much time passes each tick of a headless run.
+pub const HEADLESS_TICK_SECONDS: f64 = 1.0 / 60.0;
+
+/// How long we wait for the config and scene to load before giving up.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `DefaultPlugins` without a renderer, audio or an OS window, so the game can
+/// run on machines without a GPU or sound card. A primary `Window` entity still
+/// exists so systems that read it, like mouselook, behave as they normally would.
+pub fn headless_default_plugins() -> PluginGroupBuilder {
+    let plugins = DefaultPlugins
+        .set(RenderPlugin {
+            wgpu_settings: WgpuSettings {
+                backends: None,
+                ..default()
+            },
+        })
+        .disable::<WinitPlugin>();
+    #[cfg(feature = "audio")]
+    let plugins = plugins.disable::<bevy::audio::AudioPlugin>();
+    plugins
+}
+
+/// Runs the app until it's in game, then for a fixed number of ticks of
+/// `HEADLESS_TICK_SECONDS` each, and exits.
+pub struct HeadlessPlugin {
+    pub ticks: u32,
+}
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        let ticks = self.ticks;
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            HEADLESS_TICK_SECONDS,
+        )))
+        .set_runner(move |app| run_headless(app, ticks));
+    }
+}
+
+fn should_exit(app: &App, exit_reader: &mut ManualEventReader<AppExit>) -> bool {
+    let exit_events = app.world.resource::<Events<AppExit>>();
+    exit_reader.iter(exit_events).next().is_some()
+}
+
+/// Finishes setting up the app and updates it until the game has loaded and
+/// is in progress. This is what a headless run does before its ticks, and is
+/// useful for driving the app by hand with `App::update`, e.g. in tests.
+pub fn start_headless(app: &mut App) -> Result<(), String> {
+    while !app.ready() {
+        #[cfg(not(target_arch = "wasm32"))]
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+
+    let mut exit_reader = ManualEventReader::<AppExit>::default();
+    let load_start = Instant::now();
+    while *app.world.resource::<State<AppState>>().get() != AppState::InGame {
+        app.update();
+        if should_exit(app, &mut exit_reader) {
+            return Err("The app exited while loading.".to_string());
+        }
+        if load_start.elapsed() > LOAD_TIMEOUT {
+            return Err("Timed out waiting for the game to load.".to_string());
+        }
+    }
+    info!("Loaded in {:?}.", load_start.elapsed());
+    Ok(())
+}
+
+fn run_headless(mut app: App, ticks: u32) {
+    if let Err(error) = start_headless(&mut app) {
+        error!("Headless run failed: {}", error);
+        return;
+    }
+    info!("Running {} ticks...", ticks);
+
+    let mut exit_reader = ManualEventReader::<AppExit>::default();
+    for tick in 0..ticks {
+        if tick + 1 == ticks {
+            // Let systems that clean up on exit, like the input recorder, see
+            // that this is the last tick.
+            app.world.send_event(AppExit);
+        }
+        app.update();
+        if tick + 1 < ticks && should_exit(&app, &mut exit_reader) {
+            return;
+        }
+    }
+
+    let mut player_query = app.world.query_filtered::<&GlobalTransform, With<Player>>();
+    match player_query.get_single(&app.world) {
+        Ok(transform) => info!(
+            "Headless run finished after {} ticks with the player at {}.",
+            ticks,
+            transform.translation()
+        ),
+        Err(_) => info!("Headless run finished after {} ticks.", ticks),
+    }
+}
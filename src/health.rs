@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{app_state::AppState, config::Config, player::Player};
+
+/// A volume that hurts anything with `Health` that overlaps it.
+#[derive(Component)]
+pub struct Hazard;
+
+/// A volume that becomes the player's respawn point when they enter it.
+#[derive(Component)]
+pub struct Checkpoint;
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    /// When set, damage has no effect.
+    pub invulnerable: bool,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Health {
+            current: max,
+            max,
+            invulnerable: false,
+        }
+    }
+
+    pub fn damage(&mut self, amount: f32) {
+        if !self.invulnerable {
+            self.current = (self.current - amount).max(0.0);
+        }
+    }
+
+    pub fn restore(&mut self) {
+        self.current = self.max;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+/// Added to an entity whose health has run out. The timer tracks how far
+/// along they are in fading out before they respawn.
+#[derive(Component)]
+pub struct Dying(pub Timer);
+
+/// The position the player will respawn at after dying.
+#[derive(Resource, Default)]
+pub struct LastCheckpoint(pub Vec3);
+
+#[derive(Component)]
+struct DeathFade;
+
+fn setup_health(mut commands: Commands, config: Res<Config>) {
+    commands.insert_resource(LastCheckpoint(config.spawn_position));
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.0).into(),
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+        DeathFade,
+        Name::new("DeathFade"),
+    ));
+}
+
+fn despawn_death_fade(mut commands: Commands, query: Query<Entity, With<DeathFade>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn apply_hazard_damage(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Health, &Transform, &Collider), Without<Dying>>,
+    hazards: Query<(), With<Hazard>>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+) {
+    for (entity, mut health, transform, collider) in query.iter_mut() {
+        let is_hazard = |entity| hazards.contains(entity);
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .predicate(&is_hazard);
+        let mut in_hazard = false;
+        rapier_context.intersections_with_shape(
+            transform.translation,
+            transform.rotation,
+            collider,
+            filter,
+            |_| {
+                in_hazard = true;
+                false
+            },
+        );
+        if in_hazard {
+            health.damage(config.hazard_damage_per_second * time.delta_seconds());
+        }
+    }
+}
+
+fn update_last_checkpoint(
+    player_query: Query<(Entity, &Transform, &Collider), (With<Player>, Without<Dying>)>,
+    checkpoints: Query<&GlobalTransform, With<Checkpoint>>,
+    rapier_context: Res<RapierContext>,
+    mut last_checkpoint: ResMut<LastCheckpoint>,
+) {
+    for (entity, transform, collider) in &player_query {
+        let is_checkpoint = |entity| checkpoints.contains(entity);
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .predicate(&is_checkpoint);
+        rapier_context.intersections_with_shape(
+            transform.translation,
+            transform.rotation,
+            collider,
+            filter,
+            |checkpoint| {
+                let Ok(checkpoint_transform) = checkpoints.get(checkpoint) else {
+                    return true;
+                };
+                // Checkpoints are positioned with their origin on the floor, just like
+                // the spawn position.
+                let position = checkpoint_transform.translation();
+                if last_checkpoint.0 != position {
+                    info!("Reached checkpoint at {}.", position);
+                    last_checkpoint.0 = position;
+                }
+                false
+            },
+        );
+    }
+}
+
+fn start_dying(
+    mut commands: Commands,
+    query: Query<(Entity, &Health), Without<Dying>>,
+    config: Res<Config>,
+) {
+    for (entity, health) in &query {
+        if health.is_dead() {
+            info!("Entity {:?} died.", entity);
+            commands.entity(entity).insert(Dying(Timer::from_seconds(
+                config.death_fade_duration,
+                TimerMode::Once,
+            )));
+        }
+    }
+}
+
+fn tick_dying(time: Res<Time>, mut query: Query<&mut Dying>) {
+    for mut dying in &mut query {
+        dying.0.tick(time.delta());
+    }
+}
+
+fn update_death_fade(
+    time: Res<Time>,
+    player_query: Query<Option<&Dying>, With<Player>>,
+    mut fade_query: Query<&mut BackgroundColor, With<DeathFade>>,
+    config: Res<Config>,
+) {
+    let Ok(dying) = player_query.get_single() else {
+        return;
+    };
+    for mut background in &mut fade_query {
+        let alpha = match dying {
+            Some(dying) => dying.0.percent(),
+            // We've respawned, so fade back in.
+            None => background.0.a() - time.delta_seconds() / config.death_fade_duration,
+        };
+        background.0.set_a(alpha.clamp(0.0, 1.0));
+    }
+}
+
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LastCheckpoint>()
+            .add_systems(OnEnter(AppState::SettingUpScene), setup_health)
+            .add_systems(OnExit(AppState::InGame), despawn_death_fade)
+            .add_systems(
+                Update,
+                (
+                    apply_hazard_damage,
+                    update_last_checkpoint,
+                    start_dying.after(apply_hazard_damage),
+                    tick_dying,
+                    update_death_fade.after(tick_dying),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
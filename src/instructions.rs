@@ -9,32 +9,78 @@ use crate::{
 #[derive(Component)]
 struct InstructionText;
 
+/// The size of text shown over the game, like the instructions.
+pub(crate) const HUD_FONT_SIZE: f32 = 30.0;
+
+/// The fonts for text shown over the game. Other plugins that show text, like
+/// the objectives, share them by initializing this resource too.
 #[derive(Resource)]
-struct Fonts {
+pub(crate) struct Fonts {
     fira_sans_bold: Handle<Font>,
 }
 
-fn load_fonts(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut loading: ResMut<AssetsLoading>,
+impl FromWorld for Fonts {
+    fn from_world(world: &mut World) -> Self {
+        Fonts {
+            fira_sans_bold: world
+                .resource::<AssetServer>()
+                .load("fonts/FiraSans-Bold.ttf"),
+        }
+    }
+}
+
+impl Fonts {
+    pub(crate) fn hud_text_style(&self, font_size: f32) -> TextStyle {
+        TextStyle {
+            font: self.fira_sans_bold.clone(),
+            font_size,
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Spawns an empty line of HUD text, centered across the screen. `position`
+/// says where its row goes, e.g. `top: Val::Px(10.0)`. `row` is added to the
+/// row and `text` to the text, so it can be found and updated later.
+pub(crate) fn spawn_centered_hud_text(
+    commands: &mut Commands,
+    fonts: &Fonts,
+    position: Style,
+    row: impl Bundle,
+    text: impl Bundle,
 ) {
-    let fonts = Fonts {
-        fira_sans_bold: asset_server.load("fonts/FiraSans-Bold.ttf"),
-    };
-    let mut untyped_fonts = vec![fonts.fira_sans_bold.clone_untyped()];
-    loading.0.append(&mut untyped_fonts);
-    commands.insert_resource(fonts);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    ..position
+                },
+                ..default()
+            },
+            row,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", fonts.hud_text_style(HUD_FONT_SIZE)),
+                text,
+            ));
+        });
+}
+
+fn load_fonts(fonts: Res<Fonts>, mut loading: ResMut<AssetsLoading>) {
+    loading.0.push(fonts.fira_sans_bold.clone_untyped());
 }
 
 fn show_instructions(mut commands: Commands, fonts: Res<Fonts>, config: Res<Config>) {
-    let text_style = TextStyle {
-        font: fonts.fira_sans_bold.clone(),
-        font_size: 30.0,
-        color: Color::WHITE,
-    };
+    let text_style = fonts.hud_text_style(HUD_FONT_SIZE);
+    let instructions = config
+        .instructions
+        .replace("{use_key}", &config.use_key.to_string());
     commands.spawn((
-        TextBundle::from_section(config.instructions.clone(), text_style)
+        TextBundle::from_section(instructions, text_style)
             .with_text_alignment(TextAlignment::Left)
             .with_style(Style {
                 position_type: PositionType::Absolute,
@@ -59,12 +105,20 @@ fn hide_instructions(
     }
 }
 
+fn despawn_instructions(mut commands: Commands, query: Query<Entity, With<InstructionText>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
 pub struct InstructionsPlugin;
 
 impl Plugin for InstructionsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, load_fonts)
+        app.init_resource::<Fonts>()
+            .add_systems(Startup, load_fonts)
             .add_systems(OnEnter(AppState::InGame), show_instructions)
+            .add_systems(OnExit(AppState::InGame), despawn_instructions)
             .add_systems(Update, hide_instructions.run_if(in_state(AppState::InGame)));
     }
 }
@@ -0,0 +1,173 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    debug_mode::is_in_debug_mode,
+    health::Dying,
+    instructions::{spawn_centered_hud_text, Fonts},
+    player::{look_ray, Player},
+};
+
+/// Something the player can use by looking at it and pressing the use key,
+/// e.g. a lever, door, note or torch. It needs a collider for the player's
+/// view to hit, which can be a sensor.
+#[derive(Component)]
+pub struct Interactable {
+    /// What using it does, e.g. `Pull lever`, which is shown after the use
+    /// key when the player looks at it.
+    pub prompt: String,
+    /// Whether it can be used right now, e.g. a torch can only be lit when
+    /// it's out.
+    pub enabled: bool,
+}
+
+impl Interactable {
+    pub fn new(prompt: &str) -> Self {
+        Interactable {
+            prompt: prompt.to_string(),
+            enabled: true,
+        }
+    }
+}
+
+/// Sent when the player uses an `Interactable`. Whatever the interactable is
+/// handles this by checking whether `entity` is one of its own.
+#[derive(Event)]
+pub struct Interacted {
+    pub entity: Entity,
+}
+
+/// The interactable that the player is looking at and is close enough to use,
+/// if any.
+#[derive(Resource, Default)]
+pub struct InteractionTarget(pub Option<Entity>);
+
+#[derive(Component)]
+struct InteractionPrompt;
+
+#[derive(Component)]
+struct InteractionPromptText;
+
+fn find_interaction_target(
+    player_query: Query<Entity, (With<Player>, Without<Dying>)>,
+    camera_query: Query<(&Parent, &Transform, &GlobalTransform), With<Camera3d>>,
+    interactables: Query<&Interactable>,
+    sensors: Query<(), With<Sensor>>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+    mut target: ResMut<InteractionTarget>,
+) {
+    let mut new_target = None;
+    for (parent, transform, global_transform) in &camera_query {
+        let Ok(player) = player_query.get(parent.get()) else {
+            continue;
+        };
+        let (ray_pos, ray_dir) = look_ray(transform, global_transform);
+        // Sensors are invisible, so they only get in the way if they're
+        // what's being looked for.
+        let is_visible = |entity| !sensors.contains(entity) || interactables.contains(entity);
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(player)
+            .predicate(&is_visible);
+        if let Some((entity, _)) = rapier_context.cast_ray(
+            ray_pos,
+            ray_dir,
+            config.interaction_max_distance,
+            true,
+            filter,
+        ) {
+            if interactables
+                .get(entity)
+                .is_ok_and(|interactable| interactable.enabled)
+            {
+                new_target = Some(entity);
+            }
+        }
+    }
+    // Avoid needlessly triggering change detection.
+    if target.0 != new_target {
+        target.0 = new_target;
+    }
+}
+
+fn use_interaction_target(
+    keys: Res<Input<KeyCode>>,
+    target: Res<InteractionTarget>,
+    mut interactions: EventWriter<Interacted>,
+    config: Res<Config>,
+) {
+    if !keys.just_pressed(config.use_key.0) {
+        return;
+    }
+    if let Some(entity) = target.0 {
+        interactions.send(Interacted { entity });
+    }
+}
+
+fn setup_interaction_prompt(mut commands: Commands, fonts: Res<Fonts>) {
+    spawn_centered_hud_text(
+        &mut commands,
+        &fonts,
+        Style {
+            // Just below the middle of the screen, where the player is looking.
+            top: Val::Percent(55.0),
+            ..default()
+        },
+        (InteractionPrompt, Name::new("InteractionPrompt")),
+        InteractionPromptText,
+    );
+}
+
+fn update_interaction_prompt(
+    target: Res<InteractionTarget>,
+    interactables: Query<&Interactable>,
+    config: Res<Config>,
+    mut prompt_query: Query<&mut Text, With<InteractionPromptText>>,
+) {
+    let prompt = match target.0.and_then(|entity| interactables.get(entity).ok()) {
+        Some(interactable) => format!("{}: {}", config.use_key, interactable.prompt),
+        None => String::new(),
+    };
+    for mut text in &mut prompt_query {
+        if text.sections[0].value != prompt {
+            text.sections[0].value = prompt.clone();
+        }
+    }
+}
+
+fn despawn_interaction_prompt(
+    mut commands: Commands,
+    query: Query<Entity, With<InteractionPrompt>>,
+    mut target: ResMut<InteractionTarget>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+    target.0 = None;
+}
+
+/// Lets the player use whatever `Interactable` they're looking at with the use
+/// key, showing a prompt for what it'll do.
+pub struct InteractionPlugin;
+
+impl Plugin for InteractionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Interacted>()
+            .init_resource::<InteractionTarget>()
+            .init_resource::<Fonts>()
+            .add_systems(OnEnter(AppState::InGame), setup_interaction_prompt)
+            .add_systems(OnExit(AppState::InGame), despawn_interaction_prompt)
+            .add_systems(
+                Update,
+                (
+                    find_interaction_target,
+                    use_interaction_target.run_if(not(is_in_debug_mode)),
+                    update_interaction_prompt,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
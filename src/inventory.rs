@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    collectible::{Collected, CollectibleKind},
+};
+
+/// Something the player has picked up.
+#[derive(serde::Deserialize, serde::Serialize, Clone, PartialEq, Debug)]
+pub struct InventoryItem {
+    pub kind: CollectibleKind,
+    /// The item's name without its kind, e.g. `Gold` for the `Gold-key`.
+    pub name: String,
+}
+
+/// Everything the player is carrying, in the order it was picked up.
+#[derive(Resource, Default)]
+pub struct Inventory {
+    pub items: Vec<InventoryItem>,
+}
+
+impl Inventory {
+    /// Whether the player has the key with the given name, e.g. `Gold`.
+    pub fn has_key(&self, name: &str) -> bool {
+        self.items
+            .iter()
+            .any(|item| item.kind == CollectibleKind::Key && item.name == name)
+    }
+
+    pub fn count(&self, kind: CollectibleKind) -> usize {
+        self.items.iter().filter(|item| item.kind == kind).count()
+    }
+
+    /// What to show in the inventory strip, with all the coins in one slot
+    /// since they're all alike.
+    fn labels(&self) -> Vec<String> {
+        let mut labels: Vec<String> = self
+            .items
+            .iter()
+            .filter(|item| item.kind != CollectibleKind::Coin)
+            .map(|item| match item.kind {
+                CollectibleKind::Key => format!("{} key", item.name),
+                _ => format!("{} relic", item.name),
+            })
+            .collect();
+        match self.count(CollectibleKind::Coin) {
+            0 => {}
+            1 => labels.insert(0, "1 coin".to_string()),
+            coins => labels.insert(0, format!("{} coins", coins)),
+        }
+        labels
+    }
+}
+
+/// The row of held items along the bottom of the screen.
+#[derive(Component)]
+struct InventoryStrip;
+
+fn reset_inventory(mut commands: Commands) {
+    commands.insert_resource(Inventory::default());
+}
+
+fn add_collected_items(mut events: EventReader<Collected>, mut inventory: ResMut<Inventory>) {
+    for event in events.iter() {
+        inventory.items.push(InventoryItem {
+            kind: event.kind,
+            name: event.name.clone(),
+        });
+    }
+}
+
+fn setup_inventory_strip(mut commands: Commands) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(10.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        },
+        InventoryStrip,
+        Name::new("InventoryStrip"),
+    ));
+}
+
+fn update_inventory_strip(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    inventory: Res<Inventory>,
+    query: Query<Entity, With<InventoryStrip>>,
+) {
+    let Ok(strip) = query.get_single() else {
+        return;
+    };
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    commands
+        .entity(strip)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for label in inventory.labels() {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            padding: UiRect::axes(Val::Px(10.0), Val::Px(5.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                        ..default()
+                    })
+                    .with_children(|slot| {
+                        slot.spawn(TextBundle::from_section(label, text_style.clone()));
+                    });
+            }
+        });
+}
+
+fn despawn_inventory_strip(mut commands: Commands, query: Query<Entity, With<InventoryStrip>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Keeps what the player has picked up in the current level, e.g. keys for
+/// doors, and shows it at the bottom of the screen.
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .add_systems(OnEnter(AppState::SettingUpScene), reset_inventory)
+            .add_systems(OnEnter(AppState::InGame), setup_inventory_strip)
+            .add_systems(OnExit(AppState::InGame), despawn_inventory_strip)
+            .add_systems(
+                Update,
+                (
+                    add_collected_items,
+                    update_inventory_strip.run_if(resource_changed::<Inventory>()),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
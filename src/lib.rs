@@ -0,0 +1,163 @@
+// Bevy systems routinely take lots of parameters and complex query types.
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
+pub mod app_state;
+pub mod camera_effects;
+pub mod collectible;
+pub mod config;
+pub mod console;
+pub mod debug_mode;
+pub mod door;
+pub mod dungeon_scene;
+pub mod enemy;
+pub mod headless;
+pub mod health;
+pub mod instructions;
+pub mod interaction;
+pub mod inventory;
+pub mod light_manager;
+pub mod navmesh;
+pub mod objectives;
+pub mod perf_overlay;
+pub mod player;
+pub mod replay;
+pub mod save_game;
+#[cfg(feature = "audio")]
+pub mod sound;
+pub mod time_scale;
+pub mod torch;
+
+use app_state::AppState;
+use bevy::{app::PluginGroupBuilder, input::keyboard, prelude::*};
+use bevy_rapier3d::prelude::*;
+use camera_effects::CameraEffectsPlugin;
+use collectible::CollectiblePlugin;
+use config::ConfigPlugin;
+use debug_mode::DebugModePlugin;
+use door::DoorPlugin;
+use dungeon_scene::DungeonScenePlugin;
+use enemy::EnemyPlugin;
+use health::HealthPlugin;
+use instructions::InstructionsPlugin;
+use interaction::InteractionPlugin;
+use inventory::InventoryPlugin;
+use light_manager::LightManagerPlugin;
+use navmesh::NavMeshPlugin;
+use objectives::ObjectivesPlugin;
+use perf_overlay::PerfOverlayPlugin;
+use player::PlayerPlugin;
+use save_game::SaveGamePlugin;
+#[cfg(feature = "audio")]
+use sound::SoundPlugin;
+use time_scale::{TimeScalePlugin, FIXED_TIMESTEP_SECONDS};
+use torch::TorchPlugin;
+
+/// Everything that makes up the game, on top of `DefaultPlugins` (or
+/// `headless::headless_default_plugins`). Individual plugins can be configured,
+/// replaced or disabled through the `PluginGroupBuilder`, e.g.
+/// `GamePlugins.build().disable::<InstructionsPlugin>()`.
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(self) -> PluginGroupBuilder {
+        let group = PluginGroupBuilder::start::<Self>()
+            .add(GameCorePlugin)
+            // We step physics ourselves in `GameCorePlugin`.
+            .add(RapierPhysicsPlugin::<NoUserData>::default().with_default_system_setup(false))
+            .add(RapierDebugRenderPlugin {
+                enabled: false,
+                ..default()
+            })
+            .add(ConfigPlugin)
+            .add(PlayerPlugin)
+            .add(CameraEffectsPlugin)
+            .add(DungeonScenePlugin::default())
+            .add(HealthPlugin)
+            .add(InstructionsPlugin)
+            .add(DebugModePlugin);
+        // Plugins register their console commands either way, so they can still
+        // be run, e.g. by tests.
+        #[cfg(feature = "debug_mode")]
+        let group = group.add(console::ConsolePlugin);
+        let group = group
+            .add(TimeScalePlugin)
+            .add(PerfOverlayPlugin::default())
+            .add(SaveGamePlugin::default());
+        #[cfg(feature = "audio")]
+        let group = group.add(SoundPlugin);
+        group
+            .add(TorchPlugin)
+            .add(LightManagerPlugin)
+            .add(EnemyPlugin)
+            .add(NavMeshPlugin::default())
+            .add(CollectiblePlugin)
+            .add(ObjectivesPlugin)
+            .add(InventoryPlugin)
+            .add(DoorPlugin)
+            .add(InteractionPlugin)
+    }
+}
+
+/// The app state and global settings that the rest of the game relies on,
+/// including stepping physics once per fixed timestep.
+pub struct GameCorePlugin;
+
+impl Plugin for GameCorePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<AppState>()
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: FIXED_TIMESTEP_SECONDS as f32,
+                    substeps: 1,
+                },
+                ..default()
+            })
+            .configure_sets(
+                FixedUpdate,
+                (
+                    PhysicsSet::SyncBackend,
+                    PhysicsSet::SyncBackendFlush,
+                    PhysicsSet::StepSimulation,
+                    PhysicsSet::Writeback,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackend)
+                        .in_set(PhysicsSet::SyncBackend),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::SyncBackendFlush)
+                        .in_set(PhysicsSet::SyncBackendFlush),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::StepSimulation)
+                        .in_set(PhysicsSet::StepSimulation),
+                    RapierPhysicsPlugin::<NoUserData>::get_systems(PhysicsSet::Writeback)
+                        .in_set(PhysicsSet::Writeback),
+                ),
+            )
+            .insert_resource(Msaa::Sample4)
+            .insert_resource(GizmoConfig {
+                enabled: true,
+                depth_bias: -1.0,
+                ..Default::default()
+            })
+            .add_systems(Update, bevy::window::close_on_esc.run_if(is_not_wasm))
+            .add_systems(
+                Update,
+                toggle_rapier_debug_render_mode.run_if(resource_exists::<DebugRenderContext>()),
+            );
+    }
+}
+
+fn is_not_wasm() -> bool {
+    !cfg!(target_arch = "wasm32")
+}
+
+fn toggle_rapier_debug_render_mode(
+    keyboard_input: Res<Input<keyboard::KeyCode>>,
+    mut context: ResMut<DebugRenderContext>,
+) {
+    if keyboard_input.just_pressed(keyboard::KeyCode::G) {
+        context.enabled = !context.enabled;
+    }
+}
@@ -0,0 +1,159 @@
+use bevy::{
+    pbr::PointLightShadowMap,
+    prelude::*,
+    render::primitives::{Frustum, Sphere},
+};
+
+use crate::{app_state::AppState, config::Config};
+
+/// A point light whose shadows and brightness are managed by the
+/// `LightManagerPlugin`. Other systems should change the light through this
+/// rather than through its `PointLight` and `Visibility`, which get
+/// overwritten.
+#[derive(Component)]
+pub struct ManagedLight {
+    /// How bright the light is before it's faded out with distance.
+    pub intensity: f32,
+    /// Whether the light is on at all, e.g. whether its torch is lit.
+    pub enabled: bool,
+}
+
+/// Takes over every point light in the scene. They've all been set to cast
+/// shadows in Blender, but either Blender's glTF exporter doesn't export this,
+/// or bevy doesn't import it, so we decide which do ourselves.
+fn manage_scene_lights(
+    mut commands: Commands,
+    lights: Query<(Entity, &PointLight), Without<ManagedLight>>,
+) {
+    for (entity, light) in &lights {
+        commands.entity(entity).insert(ManagedLight {
+            intensity: light.intensity,
+            enabled: true,
+        });
+    }
+}
+
+/// How much of a light is left after fading it out with distance from the
+/// camera, from 1 when it's close to 0 when it's too far away to bother with.
+fn distance_fade(distance: f32, config: &Config) -> f32 {
+    let fade_length = config.light_fade_end_distance - config.light_fade_start_distance;
+    if fade_length <= 0.0 {
+        return if distance < config.light_fade_end_distance {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    ((config.light_fade_end_distance - distance) / fade_length).clamp(0.0, 1.0)
+}
+
+/// Fades out distant lights and gives shadows to the lights that are most
+/// likely to be noticed: those that light up something in view, closest first.
+pub(crate) fn update_managed_lights(
+    mut lights: Query<(
+        Entity,
+        &ManagedLight,
+        &GlobalTransform,
+        &mut PointLight,
+        &mut Visibility,
+    )>,
+    camera_query: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
+    config: Res<Config>,
+) {
+    let Ok((camera, frustum)) = camera_query.get_single() else {
+        return;
+    };
+    let camera_position = camera.translation();
+
+    let mut ranked: Vec<(Entity, bool, f32)> = lights
+        .iter()
+        .filter(|(_, managed, ..)| managed.enabled)
+        .map(|(entity, _, transform, light, _)| {
+            let position = transform.translation();
+            let lights_view = frustum.intersects_sphere(
+                &Sphere {
+                    center: position.into(),
+                    radius: light.range,
+                },
+                true,
+            );
+            (entity, lights_view, position.distance(camera_position))
+        })
+        .filter(|(_, _, distance)| distance_fade(*distance, &config) > 0.0)
+        .collect();
+    ranked.sort_by(|(_, a_in_view, a), (_, b_in_view, b)| {
+        b_in_view.cmp(a_in_view).then(a.total_cmp(b))
+    });
+    let budget = if config.low_quality {
+        config.low_quality_max_shadowed_lights
+    } else {
+        config.max_shadowed_lights
+    };
+    let shadowed: Vec<Entity> = ranked
+        .iter()
+        .filter(|(_, lights_view, _)| *lights_view)
+        .take(budget as usize)
+        .map(|(entity, ..)| *entity)
+        .collect();
+
+    for (entity, managed, transform, mut light, mut visibility) in &mut lights {
+        let fade = if managed.enabled {
+            distance_fade(transform.translation().distance(camera_position), &config)
+        } else {
+            0.0
+        };
+        let new_visibility = if fade > 0.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        // Avoid needlessly triggering change detection, since most lights
+        // won't change most frames.
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+        let intensity = managed.intensity * fade;
+        if light.intensity != intensity {
+            light.intensity = intensity;
+        }
+        let shadows_enabled = shadowed.contains(&entity);
+        if light.shadows_enabled != shadows_enabled {
+            light.shadows_enabled = shadows_enabled;
+        }
+    }
+}
+
+fn apply_shadow_map_size(mut commands: Commands, config: Res<Config>) {
+    let size = if config.low_quality {
+        config.low_quality_shadow_map_size
+    } else {
+        config.shadow_map_size
+    };
+    commands.insert_resource(PointLightShadowMap {
+        size: size as usize,
+    });
+}
+
+/// Keeps the cost of point lights down in big levels, by only letting a few of
+/// them cast shadows and turning off those that are far from the camera.
+pub struct LightManagerPlugin;
+
+impl Plugin for LightManagerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::SettingUpScene), manage_scene_lights)
+            .add_systems(
+                Update,
+                (
+                    // Picks up any lights added after the scene was set up.
+                    manage_scene_lights,
+                    update_managed_lights,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                apply_shadow_map_size.run_if(resource_changed::<Config>()),
+            );
+    }
+}
@@ -1,66 +1,75 @@
-mod app_state;
-mod config;
-mod debug_mode;
-mod dungeon_scene;
-mod instructions;
-mod player;
+use bevy::{prelude::*, window::WindowMode};
+use bevy_fun2::{
+    headless::{headless_default_plugins, HeadlessPlugin},
+    navmesh::NavMeshPlugin,
+    perf_overlay::PerfOverlayPlugin,
+    replay::{RecordPlugin, Recording, ReplayPlugin},
+    GamePlugins,
+};
 
-use app_state::AppState;
-use bevy::{input::keyboard, pbr::PointLightShadowMap, prelude::*, window::WindowMode};
-use bevy_rapier3d::prelude::*;
-use config::ConfigPlugin;
-use debug_mode::DebugModePlugin;
-use dungeon_scene::DungeonScenePlugin;
-use instructions::InstructionsPlugin;
-use player::PlayerPlugin;
+/// How many ticks a headless run lasts when no count is given.
+const DEFAULT_HEADLESS_TICKS: u32 = 600;
+
+/// Returns the value following a command-line flag, e.g. `--replay <file>`.
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let index = args.iter().position(|a| a == flag)?;
+    args.get(index + 1).map(String::as_str)
+}
 
 fn main() {
-    let windowed = std::env::args().any(|a| a == "--windowed" || a == "-w");
-    let mode = if windowed {
-        WindowMode::Windowed
+    let args: Vec<String> = std::env::args().collect();
+    let headless = args.iter().any(|a| a == "--headless");
+    let recording = flag_value(&args, "--replay").map(|path| {
+        Recording::load(path).unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        })
+    });
+
+    let mut app = App::new();
+    if headless {
+        let ticks = match &recording {
+            // The replay exits the app itself once it's over.
+            Some(recording) => recording.ticks.len() as u32 + 1,
+            None => flag_value(&args, "--headless")
+                .and_then(|ticks| ticks.parse().ok())
+                .unwrap_or(DEFAULT_HEADLESS_TICKS),
+        };
+        app.add_plugins(headless_default_plugins())
+            .add_plugins(HeadlessPlugin { ticks });
     } else {
-        WindowMode::BorderlessFullscreen
-    };
-    App::new()
-        .add_state::<AppState>()
-        .insert_resource(PointLightShadowMap { size: 4096 })
-        .insert_resource(Msaa::Sample4)
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+        let windowed = args.iter().any(|a| a == "--windowed" || a == "-w");
+        let mode = if windowed {
+            WindowMode::Windowed
+        } else {
+            WindowMode::BorderlessFullscreen
+        };
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 mode,
                 ..Default::default()
             }),
             ..Default::default()
-        }))
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
-        .add_plugins(RapierDebugRenderPlugin {
-            enabled: false,
-            ..default()
-        })
-        .insert_resource(GizmoConfig {
-            enabled: true,
-            depth_bias: -1.0,
-            ..Default::default()
-        })
-        .add_systems(Update, bevy::window::close_on_esc.run_if(is_not_wasm))
-        .add_plugins(ConfigPlugin)
-        .add_plugins(PlayerPlugin)
-        .add_plugins(DungeonScenePlugin)
-        .add_plugins(InstructionsPlugin)
-        .add_plugins(DebugModePlugin)
-        .add_systems(Update, toggle_rapier_debug_render_mode)
-        .run();
-}
-
-fn is_not_wasm() -> bool {
-    !cfg!(target_arch = "wasm32")
-}
-
-fn toggle_rapier_debug_render_mode(
-    keyboard_input: Res<Input<keyboard::KeyCode>>,
-    mut context: ResMut<DebugRenderContext>,
-) {
-    if keyboard_input.just_pressed(keyboard::KeyCode::G) {
-        context.enabled = !context.enabled;
+        }));
+    }
+    let bake_navmesh = args.iter().any(|a| a == "--bake-navmesh");
+    let perf_log_path = flag_value(&args, "--perf-log").map(str::to_string);
+    app.add_plugins(
+        GamePlugins
+            .build()
+            .set(NavMeshPlugin { bake: bake_navmesh })
+            .set(PerfOverlayPlugin {
+                log_path: perf_log_path,
+            }),
+    );
+    if let Some(path) = flag_value(&args, "--record") {
+        app.add_plugins(RecordPlugin { path: path.into() });
+    }
+    if let Some(recording) = recording {
+        app.add_plugins(ReplayPlugin {
+            recording,
+            exit_when_finished: headless,
+        });
     }
+    app.run();
 }
@@ -0,0 +1,725 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use bevy::{
+    prelude::*,
+    reflect::{TypePath, TypeUuid},
+};
+use bevy_common_assets::json::JsonAssetPlugin;
+use bevy_rapier3d::{
+    prelude::*,
+    rapier::{
+        self,
+        math::{Isometry, Point, Real},
+        parry::shape::Shape,
+    },
+};
+
+use crate::{
+    app_state::AppState, config::Config, debug_mode::is_in_debug_mode, dungeon_scene::CurrentLevel,
+};
+
+/// Surfaces closer together than this, vertically, are treated as one.
+const MIN_SURFACE_SEPARATION: f32 = 0.05;
+
+/// How far from the navmesh, horizontally, a point given to a path query can
+/// be and still be snapped onto it.
+const MAX_SNAP_DISTANCE: f32 = 1.0;
+
+/// Only the part of the navmesh this close to the camera is drawn in debug
+/// mode, since drawing all of it would be slow in big levels.
+const DEBUG_DRAW_DISTANCE: f32 = 15.0;
+
+/// The dimensions of the agent a navmesh is generated for, and how finely it's
+/// sampled. A cached navmesh is only used if these match the config, and the
+/// level's geometry hasn't changed since it was baked.
+#[derive(serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct NavMeshSettings {
+    pub agent_radius: f32,
+    /// The height of the agent from the bottom of its capsule to the top.
+    pub agent_height: f32,
+    pub max_step_height: f32,
+    /// The steepest slope the agent can walk up, in degrees.
+    pub max_slope_angle: f32,
+    /// The width of each cell of the grid the level is sampled on, in meters.
+    /// This should be less than twice the agent's radius, or the navmesh may
+    /// connect cells on either side of a thin wall.
+    pub cell_size: f32,
+}
+
+impl NavMeshSettings {
+    /// Settings for an agent the size of the player, who can climb the same
+    /// steps and slopes.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            agent_radius: config.player_capsule_radius,
+            agent_height: config.player_capsule_cylinder_height
+                + config.player_capsule_radius * 2.0,
+            max_step_height: config.player_autostep_max_height,
+            max_slope_angle: config.player_max_slope_climb_angle,
+            cell_size: config.navmesh_cell_size,
+        }
+    }
+}
+
+/// A walkable surface in one cell of the navmesh.
+#[derive(serde::Deserialize, serde::Serialize, Clone)]
+struct NavNode {
+    /// The index of the cell's column, which is `z * columns_x + x`.
+    column: u32,
+    /// The height of the surface at the middle of the cell.
+    height: f32,
+    /// The nodes in neighboring cells that can be walked to from this one.
+    neighbors: Vec<u32>,
+}
+
+/// Where an agent can walk in the level, generated from its static colliders.
+///
+/// Rather than polygons, this is a grid of cells over the level seen from
+/// above, with a node for each walkable surface in a cell. A cell can have
+/// more than one, e.g. a bridge with a floor below it. Nodes in neighboring
+/// cells are connected when the agent can step or walk up a slope between
+/// them.
+///
+/// It's both an asset, so it can be baked ahead of time, and a resource for the
+/// current level.
+#[derive(serde::Deserialize, serde::Serialize, TypeUuid, TypePath, Resource, Clone)]
+#[uuid = "0c4b4d9e-6f3e-4a51-9d1c-7b2f1e0a8c35"]
+pub struct NavMesh {
+    pub settings: NavMeshSettings,
+    /// A hash of the static colliders it was generated from, so that a baked
+    /// navmesh isn't used once the level has changed.
+    geometry_hash: u64,
+    /// The corner of the grid with the lowest x and z.
+    origin: Vec2,
+    columns_x: u32,
+    columns_z: u32,
+    /// Where each column's nodes start in `nodes`, followed by the number of
+    /// nodes.
+    column_starts: Vec<u32>,
+    nodes: Vec<NavNode>,
+}
+
+/// An entry in the open set of the A* search, ordered so the `BinaryHeap`
+/// pops the lowest estimated cost first.
+struct OpenNode {
+    estimate: f32,
+    node: u32,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is guaranteed to give the same hashes
+/// with every version of Rust, so baked navmeshes stay valid.
+struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Fnv1aHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Hashes a coordinate rounded to the nearest millimeter, so that tiny
+/// differences in how the level's transforms are calculated don't matter.
+fn hash_coordinate(value: f32, hasher: &mut impl Hasher) {
+    ((value * 1000.0).round() as i64).hash(hasher);
+}
+
+fn hash_point(point: &Point<Real>, hasher: &mut impl Hasher) {
+    for coordinate in point.coords.iter() {
+        hash_coordinate(*coordinate, hasher);
+    }
+}
+
+fn hash_shape(position: &Isometry<Real>, shape: &dyn Shape, hasher: &mut impl Hasher) {
+    for value in position.translation.vector.iter() {
+        hash_coordinate(*value, hasher);
+    }
+    for value in position.rotation.coords.iter() {
+        hash_coordinate(*value, hasher);
+    }
+    (shape.shape_type() as u8).hash(hasher);
+    let aabb = shape.compute_local_aabb();
+    hash_point(&aabb.mins, hasher);
+    hash_point(&aabb.maxs, hasher);
+    if let Some(trimesh) = shape.as_trimesh() {
+        trimesh
+            .vertices()
+            .iter()
+            .for_each(|vertex| hash_point(vertex, hasher));
+        trimesh.indices().hash(hasher);
+    } else if let Some(polyhedron) = shape.as_convex_polyhedron() {
+        polyhedron
+            .points()
+            .iter()
+            .for_each(|point| hash_point(point, hasher));
+    } else if let Some(compound) = shape.as_compound() {
+        for (part_position, part) in compound.shapes() {
+            hash_shape(part_position, &**part, hasher);
+        }
+    }
+}
+
+/// Whether a collider is one that navmeshes are generated from.
+fn is_static_collider(context: &RapierContext, collider: &rapier::geometry::Collider) -> bool {
+    let is_fixed = collider
+        .parent()
+        .and_then(|body| context.bodies.get(body))
+        .map_or(true, |body| body.is_fixed());
+    is_fixed && !collider.is_sensor()
+}
+
+/// A hash of the fixed, non-sensor colliders in the physics world, which
+/// doesn't depend on the order they were added in.
+fn hash_static_geometry(context: &RapierContext) -> u64 {
+    let mut collider_hashes: Vec<u64> = context
+        .colliders
+        .iter()
+        .filter(|(_, collider)| is_static_collider(context, collider))
+        .map(|(_, collider)| {
+            let mut hasher = Fnv1aHasher::default();
+            hash_shape(collider.position(), collider.shape(), &mut hasher);
+            hasher.finish()
+        })
+        .collect();
+    collider_hashes.sort_unstable();
+    let mut hasher = Fnv1aHasher::default();
+    collider_hashes.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl NavMesh {
+    /// Generates a navmesh from the fixed, non-sensor colliders in the physics
+    /// world, by casting rays down through each cell to find the surfaces in
+    /// it, and keeping those that are flat enough and have room for the agent.
+    /// This doesn't need a renderer, so it can be done headlessly.
+    pub fn generate(context: &RapierContext, settings: NavMeshSettings) -> Self {
+        let filter = QueryFilter::only_fixed().exclude_sensors();
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        for (_, collider) in context.colliders.iter() {
+            if !is_static_collider(context, collider) {
+                continue;
+            }
+            let aabb = collider.compute_aabb();
+            min = min.min(Vec3::from(aabb.mins) * context.physics_scale());
+            max = max.max(Vec3::from(aabb.maxs) * context.physics_scale());
+        }
+        let mut navmesh = Self {
+            settings,
+            geometry_hash: hash_static_geometry(context),
+            origin: Vec2::new(min.x, min.z),
+            columns_x: 0,
+            columns_z: 0,
+            column_starts: vec![0],
+            nodes: vec![],
+        };
+        if min.x > max.x {
+            return navmesh;
+        }
+        navmesh.columns_x = ((max.x - min.x) / settings.cell_size).ceil().max(1.0) as u32;
+        navmesh.columns_z = ((max.z - min.z) / settings.cell_size).ceil().max(1.0) as u32;
+
+        // The agent's capsule, raised so that it clears any step it could climb.
+        let clearance_height =
+            (settings.agent_height - settings.max_step_height).max(settings.agent_radius * 2.0);
+        let clearance_capsule = Collider::capsule_y(
+            clearance_height / 2.0 - settings.agent_radius,
+            settings.agent_radius,
+        );
+        let min_normal_y = settings.max_slope_angle.to_radians().cos();
+        let top = max.y + settings.agent_height + 1.0;
+        navmesh.column_starts.clear();
+        for column in 0..navmesh.columns_x * navmesh.columns_z {
+            navmesh.column_starts.push(navmesh.nodes.len() as u32);
+            let center = navmesh.column_center(column);
+            // The surface above the current one, which may be a ceiling. Trimesh
+            // colliders are hollow, so the ray also hits the undersides of
+            // floors, which face up as far as it's concerned. Requiring room
+            // above a surface rules those out, unless a floor is very thick.
+            let mut above = top;
+            while let Some((_, hit)) = context.cast_ray_and_get_normal(
+                Vec3::new(center.x, above - MIN_SURFACE_SEPARATION, center.y),
+                Vec3::NEG_Y,
+                above - min.y,
+                false,
+                filter,
+            ) {
+                let height = hit.point.y;
+                let has_headroom = above - height >= settings.agent_height;
+                let is_walkable = has_headroom
+                    && hit.normal.y >= min_normal_y
+                    && context
+                        .intersection_with_shape(
+                            Vec3::new(
+                                center.x,
+                                height + settings.max_step_height + clearance_height / 2.0,
+                                center.y,
+                            ),
+                            Quat::IDENTITY,
+                            &clearance_capsule,
+                            filter,
+                        )
+                        .is_none();
+                if is_walkable {
+                    navmesh.nodes.push(NavNode {
+                        column,
+                        height,
+                        neighbors: vec![],
+                    });
+                }
+                above = height;
+            }
+        }
+        navmesh.column_starts.push(navmesh.nodes.len() as u32);
+        navmesh.connect_nodes();
+        navmesh
+    }
+
+    /// Connects each node to those in neighboring cells that are close enough
+    /// in height to walk to. Diagonal neighbors are only connected if both of
+    /// the cells between them are, so paths don't cut corners.
+    fn connect_nodes(&mut self) {
+        let max_slope = self.settings.max_slope_angle.to_radians().tan();
+        let max_climb = |distance: f32| self.settings.max_step_height.max(distance * max_slope);
+        let straight_climb = max_climb(self.settings.cell_size);
+        let diagonal_climb = max_climb(self.settings.cell_size * std::f32::consts::SQRT_2);
+
+        let mut neighbors: Vec<Vec<u32>> = vec![vec![]; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (x, z) = self.column_coords(node.column);
+            for (dx, dz) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                if let Some(neighbor) =
+                    self.node_near_height(x + dx, z + dz, node.height, straight_climb)
+                {
+                    neighbors[index].push(neighbor);
+                }
+            }
+        }
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (x, z) = self.column_coords(node.column);
+            for (dx, dz) in [(1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let Some(neighbor) =
+                    self.node_near_height(x + dx, z + dz, node.height, diagonal_climb)
+                else {
+                    continue;
+                };
+                let connects_through = |through_x, through_z| {
+                    neighbors[index].iter().any(|&through| {
+                        self.nodes[through as usize].column
+                            == self.column_index(through_x, through_z).unwrap()
+                            && neighbors[through as usize].contains(&neighbor)
+                    })
+                };
+                if connects_through(x + dx, z) && connects_through(x, z + dz) {
+                    neighbors[index].push(neighbor);
+                }
+            }
+        }
+        for (node, neighbors) in self.nodes.iter_mut().zip(neighbors) {
+            node.neighbors = neighbors;
+        }
+    }
+
+    /// The node in the given cell whose height is closest to `height`, if
+    /// it's within `max_difference` of it.
+    fn node_near_height(&self, x: i32, z: i32, height: f32, max_difference: f32) -> Option<u32> {
+        self.column_nodes(self.column_index(x, z)?)
+            .map(|index| (index, (self.nodes[index as usize].height - height).abs()))
+            .filter(|(_, difference)| *difference <= max_difference)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    fn column_index(&self, x: i32, z: i32) -> Option<u32> {
+        if x < 0 || z < 0 || x >= self.columns_x as i32 || z >= self.columns_z as i32 {
+            return None;
+        }
+        Some(z as u32 * self.columns_x + x as u32)
+    }
+
+    fn column_coords(&self, column: u32) -> (i32, i32) {
+        (
+            (column % self.columns_x) as i32,
+            (column / self.columns_x) as i32,
+        )
+    }
+
+    fn column_center(&self, column: u32) -> Vec2 {
+        let (x, z) = self.column_coords(column);
+        self.origin + (Vec2::new(x as f32, z as f32) + 0.5) * self.settings.cell_size
+    }
+
+    /// The indices of the nodes in a column.
+    fn column_nodes(&self, column: u32) -> std::ops::Range<u32> {
+        self.column_starts[column as usize]..self.column_starts[column as usize + 1]
+    }
+
+    /// The coordinates of the cell containing a point, which may be outside
+    /// the grid.
+    fn cell_coords(&self, point: Vec3) -> (i32, i32) {
+        let cell = ((Vec2::new(point.x, point.z) - self.origin) / self.settings.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    /// Whether the navmesh was generated with the given settings from the
+    /// static colliders that are in the physics world now.
+    pub fn is_up_to_date(&self, context: &RapierContext, settings: NavMeshSettings) -> bool {
+        self.settings == settings && self.geometry_hash == hash_static_geometry(context)
+    }
+
+    /// The number of walkable surfaces in the navmesh.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Where a node is, in the middle of its cell.
+    fn node_position(&self, node: u32) -> Vec3 {
+        let node = &self.nodes[node as usize];
+        let center = self.column_center(node.column);
+        Vec3::new(center.x, node.height, center.y)
+    }
+
+    /// Finds the node that an agent at the given point is standing on, or the
+    /// nearest one within `MAX_SNAP_DISTANCE` of it. The point can be anywhere
+    /// from the agent's feet to the top of its head.
+    pub fn nearest_node(&self, point: Vec3) -> Option<u32> {
+        let (x, z) = self.cell_coords(point);
+        let reach = (MAX_SNAP_DISTANCE / self.settings.cell_size).ceil() as i32;
+        let mut nearest = None;
+        let mut nearest_distance = f32::MAX;
+        for cell_z in z - reach..=z + reach {
+            for cell_x in x - reach..=x + reach {
+                let Some(column) = self.column_index(cell_x, cell_z) else {
+                    continue;
+                };
+                for node in self.column_nodes(column) {
+                    let position = self.node_position(node);
+                    let horizontal = Vec2::new(position.x - point.x, position.z - point.z).length();
+                    let above_surface = point.y - position.y;
+                    let vertical = if above_surface < -self.settings.max_step_height {
+                        -self.settings.max_step_height - above_surface
+                    } else if above_surface > self.settings.agent_height {
+                        above_surface - self.settings.agent_height
+                    } else {
+                        0.0
+                    };
+                    let distance = horizontal.hypot(vertical);
+                    if distance <= MAX_SNAP_DISTANCE && distance < nearest_distance {
+                        nearest = Some(node);
+                        nearest_distance = distance;
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    /// Finds a path for the agent between two points, as a list of points on
+    /// the ground to walk straight between in turn. It starts and ends in the
+    /// middle of the cells nearest the points. Returns `None` if either point
+    /// isn't near the navmesh or there's no way between them.
+    pub fn find_path(&self, start: Vec3, end: Vec3) -> Option<Vec<Vec3>> {
+        let start = self.nearest_node(start)?;
+        let end = self.nearest_node(end)?;
+        let nodes = self.find_node_path(start, end)?;
+        Some(
+            self.smooth_path(&nodes)
+                .into_iter()
+                .map(|node| self.node_position(node))
+                .collect(),
+        )
+    }
+
+    /// An A* search from one node to another, returning every node on the way.
+    fn find_node_path(&self, start: u32, end: u32) -> Option<Vec<u32>> {
+        let end_position = self.node_position(end);
+        let mut costs = vec![f32::MAX; self.nodes.len()];
+        let mut came_from = vec![u32::MAX; self.nodes.len()];
+        let mut open = BinaryHeap::new();
+        costs[start as usize] = 0.0;
+        open.push(OpenNode {
+            estimate: self.node_position(start).distance(end_position),
+            node: start,
+        });
+        while let Some(OpenNode { node, estimate }) = open.pop() {
+            if node == end {
+                let mut path = vec![end];
+                let mut current = end;
+                while came_from[current as usize] != u32::MAX {
+                    current = came_from[current as usize];
+                    path.push(current);
+                }
+                path.reverse();
+                return Some(path);
+            }
+            let position = self.node_position(node);
+            // Skip stale entries for nodes we've since found a cheaper way to.
+            if estimate > costs[node as usize] + position.distance(end_position) {
+                continue;
+            }
+            for &neighbor in &self.nodes[node as usize].neighbors {
+                let neighbor_position = self.node_position(neighbor);
+                let cost = costs[node as usize] + position.distance(neighbor_position);
+                if cost < costs[neighbor as usize] {
+                    costs[neighbor as usize] = cost;
+                    came_from[neighbor as usize] = node;
+                    open.push(OpenNode {
+                        estimate: cost + neighbor_position.distance(end_position),
+                        node: neighbor,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Removes the nodes of a path that the agent can skip by walking straight
+    /// from an earlier one to a later one.
+    fn smooth_path(&self, nodes: &[u32]) -> Vec<u32> {
+        let Some(&first) = nodes.first() else {
+            return vec![];
+        };
+        let mut smoothed = vec![first];
+        let mut from = 0;
+        while from < nodes.len() - 1 {
+            let mut to = from + 1;
+            while to + 1 < nodes.len() && self.can_walk_straight(nodes[from], nodes[to + 1]) {
+                to += 1;
+            }
+            smoothed.push(nodes[to]);
+            from = to;
+        }
+        smoothed
+    }
+
+    /// Whether walking in a straight line from one node to another only
+    /// crosses connected nodes.
+    fn can_walk_straight(&self, from: u32, to: u32) -> bool {
+        let start = self.node_position(from);
+        let end = self.node_position(to);
+        let steps = (start.distance(end) / (self.settings.cell_size / 2.0)).ceil() as u32;
+        let mut current = from;
+        for step in 1..=steps {
+            let point = start.lerp(end, step as f32 / steps as f32);
+            let (x, z) = self.cell_coords(point);
+            if self.column_index(x, z) == Some(self.nodes[current as usize].column) {
+                continue;
+            }
+            let Some(&next) = self.nodes[current as usize]
+                .neighbors
+                .iter()
+                .find(|&&neighbor| {
+                    self.column_index(x, z) == Some(self.nodes[neighbor as usize].column)
+                })
+            else {
+                return false;
+            };
+            current = next;
+        }
+        current == to
+    }
+}
+
+/// The path of a level's baked navmesh, relative to the assets directory.
+fn cached_navmesh_path(level: &str) -> String {
+    format!("{}.navmesh.json", level)
+}
+
+/// The level's baked navmesh, if it has one.
+#[derive(Resource)]
+struct CachedNavMesh(Handle<NavMesh>);
+
+fn load_cached_navmesh(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    level: Res<CurrentLevel>,
+) {
+    let path = cached_navmesh_path(&level.0);
+    // Most levels won't have been baked, so check first rather than have the
+    // asset server complain about the missing file.
+    if asset_server
+        .asset_io()
+        .get_metadata(Path::new(&path))
+        .is_ok()
+    {
+        commands.insert_resource(CachedNavMesh(asset_server.load(path)));
+    }
+}
+
+/// Provides the current level's navmesh, from its baked one if that's for the
+/// same settings and level geometry, or by generating it. This waits until physics has stepped,
+/// so the level's colliders are in the physics world.
+fn provide_navmesh(
+    mut commands: Commands,
+    context: Res<RapierContext>,
+    config: Res<Config>,
+    asset_server: Res<AssetServer>,
+    cached: Option<Res<CachedNavMesh>>,
+    navmeshes: Res<Assets<NavMesh>>,
+) {
+    use bevy::asset::LoadState;
+
+    let settings = NavMeshSettings::from_config(&config);
+    if let Some(cached) = cached {
+        match asset_server.get_load_state(&cached.0) {
+            LoadState::Loading => return,
+            LoadState::Loaded => {
+                if let Some(navmesh) = navmeshes.get(&cached.0) {
+                    if navmesh.is_up_to_date(&context, settings) {
+                        info!("Using the baked navmesh.");
+                        commands.insert_resource(navmesh.clone());
+                        return;
+                    }
+                    warn!(
+                        "The baked navmesh is for a different level or settings, so generating a new one."
+                    );
+                }
+            }
+            _ => warn!("Unable to load the baked navmesh, so generating a new one."),
+        }
+    }
+    let navmesh = NavMesh::generate(&context, settings);
+    info!("Generated a navmesh with {} nodes.", navmesh.node_count());
+    commands.insert_resource(navmesh);
+}
+
+/// Throws away the navmesh if the config has changed the settings it should be
+/// generated with, so that a new one is provided.
+fn discard_outdated_navmesh(
+    mut commands: Commands,
+    navmesh: Option<Res<NavMesh>>,
+    config: Res<Config>,
+) {
+    if navmesh.is_some_and(|navmesh| navmesh.settings != NavMeshSettings::from_config(&config)) {
+        commands.remove_resource::<NavMesh>();
+    }
+}
+
+fn remove_navmesh(mut commands: Commands) {
+    commands.remove_resource::<NavMesh>();
+    commands.remove_resource::<CachedNavMesh>();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_baked_navmesh(
+    navmesh: Res<NavMesh>,
+    level: Res<CurrentLevel>,
+    mut exit: EventWriter<bevy::app::AppExit>,
+) {
+    let path = Path::new("assets").join(cached_navmesh_path(&level.0));
+    let result = serde_json::to_string(&*navmesh)
+        .map_err(|error| error.to_string())
+        .and_then(|json| std::fs::write(&path, json).map_err(|error| error.to_string()));
+    match result {
+        Ok(()) => info!("Baked the navmesh to {}.", path.display()),
+        Err(error) => error!(
+            "Unable to bake the navmesh to {}: {}",
+            path.display(),
+            error
+        ),
+    }
+    exit.send(bevy::app::AppExit);
+}
+
+fn draw_navmesh(
+    mut gizmos: Gizmos,
+    navmesh: Res<NavMesh>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    let camera_position = camera.translation();
+    // Lifted a little so the lines aren't hidden by the floor.
+    let lift = Vec3::Y * 0.05;
+    for (index, node) in navmesh.nodes.iter().enumerate() {
+        let position = navmesh.node_position(index as u32);
+        if position.distance(camera_position) > DEBUG_DRAW_DISTANCE {
+            continue;
+        }
+        for &neighbor in &node.neighbors {
+            // Each connection goes both ways, so only draw it once.
+            if neighbor > index as u32 {
+                gizmos.line(
+                    position + lift,
+                    navmesh.node_position(neighbor) + lift,
+                    Color::CYAN,
+                );
+            }
+        }
+    }
+}
+
+/// Provides a `NavMesh` resource for the current level, for pathfinding. In
+/// debug mode, the navmesh around the camera is drawn.
+#[derive(Default)]
+pub struct NavMeshPlugin {
+    /// Instead of using the level's baked navmesh, generate a new one, save it
+    /// as the level's baked navmesh and exit.
+    pub bake: bool,
+}
+
+impl Plugin for NavMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(JsonAssetPlugin::<NavMesh>::new(&["navmesh.json"]))
+            .add_systems(
+                FixedUpdate,
+                provide_navmesh
+                    .after(PhysicsSet::Writeback)
+                    .run_if(in_state(AppState::InGame).and_then(not(resource_exists::<NavMesh>()))),
+            )
+            .add_systems(
+                Update,
+                (
+                    discard_outdated_navmesh.run_if(resource_changed::<Config>()),
+                    draw_navmesh.run_if(resource_exists::<NavMesh>().and_then(is_in_debug_mode)),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(OnExit(AppState::InGame), remove_navmesh);
+        if self.bake {
+            #[cfg(not(target_arch = "wasm32"))]
+            app.add_systems(
+                Update,
+                save_baked_navmesh.run_if(resource_added::<NavMesh>()),
+            );
+        } else {
+            app.add_systems(OnEnter(AppState::LoadingAssets), load_cached_navmesh);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    collectible::{Collectible, CollectibleKind},
+    config::Config,
+    dungeon_scene::CurrentLevel,
+    health::Dying,
+    instructions::{Fonts, HUD_FONT_SIZE},
+    player::Player,
+    time_scale::FIXED_TIMESTEP_SECONDS,
+};
+
+/// Not Enter, which also runs commands typed into the console.
+const RESTART_KEY: KeyCode = KeyCode::R;
+
+/// Something the player has to do to complete a level. Each level's
+/// objectives are listed in the config, under its name.
+#[derive(serde::Deserialize, serde::Serialize, Reflect, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Objective {
+    /// Pick up `count` items of a kind, or all of them if there's no count.
+    Collect {
+        kind: CollectibleKind,
+        #[serde(default)]
+        count: Option<u32>,
+    },
+    /// Weigh down every pressure plate with something, e.g. a crate.
+    PressPlates,
+    /// Reach an exit once every other objective is done.
+    ReachExit,
+}
+
+impl Objective {
+    fn description(&self) -> String {
+        match self {
+            Objective::Collect { kind, .. } => format!("Collect {}", kind.plural()),
+            Objective::PressPlates => "Weigh down the pressure plates".to_string(),
+            Objective::ReachExit => "Reach the exit".to_string(),
+        }
+    }
+}
+
+/// A volume that completes the level when the player enters it, once every
+/// other objective is done.
+#[derive(Component)]
+pub struct Exit;
+
+/// A volume that's pressed while a dynamic body, e.g. a crate, overlaps it.
+#[derive(Component)]
+pub struct PressurePlate;
+
+/// How far along one of the level's objectives is.
+#[derive(Clone, Debug)]
+pub struct ObjectiveProgress {
+    pub objective: Objective,
+    pub done: u32,
+    pub total: u32,
+}
+
+impl ObjectiveProgress {
+    pub fn is_complete(&self) -> bool {
+        self.done >= self.total
+    }
+}
+
+/// The player's progress through the current level.
+#[derive(Resource, Default)]
+pub struct LevelProgress {
+    /// How long the level has been played for, in seconds, not counting
+    /// after it was completed.
+    pub elapsed_seconds: f32,
+    /// Whether the player has reached an exit after doing everything else.
+    pub exit_reached: bool,
+    /// Whether every objective has been done, which stays set even if, say,
+    /// a crate is later knocked off a pressure plate.
+    pub completed: bool,
+    /// The level's objectives, as of this frame.
+    pub objectives: Vec<ObjectiveProgress>,
+}
+
+#[derive(Component)]
+struct ObjectivesText;
+
+#[derive(Component)]
+struct LevelCompleteScreen;
+
+/// Formats a duration in seconds as minutes, seconds and hundredths, e.g.
+/// `2:05.31`.
+fn format_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:05.2}", minutes as u32, seconds - minutes * 60.0)
+}
+
+fn reset_level_progress(mut commands: Commands) {
+    commands.insert_resource(LevelProgress::default());
+}
+
+fn warn_about_unachievable_objectives(
+    config: Res<Config>,
+    level: Res<CurrentLevel>,
+    collectibles: Query<&Collectible>,
+    exits: Query<(), With<Exit>>,
+) {
+    for objective in config.objectives.get(&level.0).into_iter().flatten() {
+        match objective {
+            Objective::Collect {
+                kind,
+                count: Some(count),
+            } => {
+                let available = collectibles.iter().filter(|c| c.kind == *kind).count();
+                if available < *count as usize {
+                    warn!(
+                        "The level has {} {} but its objective is to collect {}.",
+                        available,
+                        kind.plural(),
+                        count
+                    );
+                }
+            }
+            Objective::ReachExit if exits.is_empty() => {
+                warn!("The level has no exit, but its objective is to reach one.");
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Runs in `FixedUpdate`, so the timer follows the simulation, e.g. stopping
+/// while it's frozen.
+fn tick_level_timer(mut progress: ResMut<LevelProgress>) {
+    if !progress.completed {
+        progress.elapsed_seconds += FIXED_TIMESTEP_SECONDS as f32;
+    }
+}
+
+fn check_exit_reached(
+    player_query: Query<(Entity, &Transform, &Collider), (With<Player>, Without<Dying>)>,
+    exits: Query<(), With<Exit>>,
+    rapier_context: Res<RapierContext>,
+    mut progress: ResMut<LevelProgress>,
+) {
+    let others_done = progress
+        .objectives
+        .iter()
+        .filter(|progress| progress.objective != Objective::ReachExit)
+        .all(ObjectiveProgress::is_complete);
+    if progress.exit_reached || !others_done {
+        return;
+    }
+    for (entity, transform, collider) in &player_query {
+        let is_exit = |entity| exits.contains(entity);
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .predicate(&is_exit);
+        if rapier_context
+            .intersection_with_shape(transform.translation, transform.rotation, collider, filter)
+            .is_some()
+        {
+            info!("Reached the exit.");
+            progress.exit_reached = true;
+        }
+    }
+}
+
+fn update_objective_progress(
+    config: Res<Config>,
+    level: Res<CurrentLevel>,
+    collectibles: Query<&Collectible>,
+    plates: Query<Entity, With<PressurePlate>>,
+    bodies: Query<&RigidBody>,
+    rapier_context: Res<RapierContext>,
+    mut progress: ResMut<LevelProgress>,
+) {
+    let objectives = config.objectives.get(&level.0).cloned().unwrap_or_default();
+    progress.objectives = objectives
+        .into_iter()
+        .map(|objective| {
+            let (done, total) = match &objective {
+                Objective::Collect { kind, count } => {
+                    let (collected, available) = collectibles
+                        .iter()
+                        .filter(|collectible| collectible.kind == *kind)
+                        .fold((0, 0), |(collected, available), collectible| {
+                            (collected + collectible.is_collected() as u32, available + 1)
+                        });
+                    (collected, count.unwrap_or(available))
+                }
+                Objective::PressPlates => {
+                    let pressed = plates
+                        .iter()
+                        .filter(|&plate| {
+                            rapier_context
+                                .intersections_with(plate)
+                                .any(|(a, b, intersecting)| {
+                                    let other = if a == plate { b } else { a };
+                                    intersecting && bodies.get(other) == Ok(&RigidBody::Dynamic)
+                                })
+                        })
+                        .count();
+                    (pressed as u32, plates.iter().count() as u32)
+                }
+                Objective::ReachExit => (progress.exit_reached as u32, 1),
+            };
+            ObjectiveProgress {
+                objective,
+                done,
+                total,
+            }
+        })
+        .collect();
+
+    if !progress.completed
+        && !progress.objectives.is_empty()
+        && progress
+            .objectives
+            .iter()
+            .all(ObjectiveProgress::is_complete)
+    {
+        info!(
+            "Completed level {} in {}.",
+            level.0,
+            format_time(progress.elapsed_seconds)
+        );
+        progress.completed = true;
+    }
+}
+
+fn setup_objectives_text(mut commands: Commands, fonts: Res<Fonts>) {
+    commands.spawn((
+        TextBundle::from_section("", fonts.hud_text_style(HUD_FONT_SIZE))
+            .with_text_alignment(TextAlignment::Right)
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(10.0),
+                // Below the time scale, when it's shown.
+                top: Val::Px(40.0),
+                ..default()
+            }),
+        ObjectivesText,
+        Name::new("ObjectivesText"),
+    ));
+}
+
+fn update_objectives_text(
+    progress: Res<LevelProgress>,
+    mut query: Query<&mut Text, With<ObjectivesText>>,
+) {
+    let lines: Vec<String> = progress
+        .objectives
+        .iter()
+        .map(|progress| {
+            let check = if progress.is_complete() { "[x]" } else { "[ ]" };
+            let description = progress.objective.description();
+            if progress.total > 1 {
+                format!(
+                    "{} {}: {}/{}",
+                    check, description, progress.done, progress.total
+                )
+            } else {
+                format!("{} {}", check, description)
+            }
+        })
+        .collect();
+    let value = lines.join("\n");
+    for mut text in &mut query {
+        // Avoid needlessly re-laying out the text every frame.
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Shows the completion screen once the level is complete, and hides it again
+/// if it isn't, e.g. after loading a save from before then.
+fn update_level_complete_screen(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    progress: Res<LevelProgress>,
+    query: Query<Entity, With<LevelCompleteScreen>>,
+) {
+    match (progress.completed, query.get_single()) {
+        (true, Err(_)) => {
+            let text = format!(
+                "Level complete!\nTime: {}\nPress R to play again.",
+                format_time(progress.elapsed_seconds)
+            );
+            commands
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                        ..default()
+                    },
+                    LevelCompleteScreen,
+                    Name::new("LevelCompleteScreen"),
+                ))
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(text, fonts.hud_text_style(48.0))
+                            .with_text_alignment(TextAlignment::Center),
+                    );
+                });
+        }
+        (false, Ok(screen)) => commands.entity(screen).despawn_recursive(),
+        _ => {}
+    }
+}
+
+fn restart_completed_level(
+    keys: Res<Input<KeyCode>>,
+    progress: Res<LevelProgress>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if progress.completed && keys.just_pressed(RESTART_KEY) {
+        info!("Restarting the level...");
+        next_state.set(AppState::LoadingAssets);
+    }
+}
+
+fn despawn_objectives_ui(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<ObjectivesText>, With<LevelCompleteScreen>)>>,
+) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Tracks the current level's objectives from the config, showing them in
+/// the corner of the screen, and shows how long the level took once they're
+/// all done.
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LevelProgress>()
+            .init_resource::<Fonts>()
+            .add_systems(OnEnter(AppState::SettingUpScene), reset_level_progress)
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (setup_objectives_text, warn_about_unachievable_objectives),
+            )
+            .add_systems(OnExit(AppState::InGame), despawn_objectives_ui)
+            .add_systems(
+                FixedUpdate,
+                tick_level_timer.run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    check_exit_reached,
+                    update_objective_progress,
+                    update_objectives_text,
+                    update_level_complete_screen,
+                    restart_completed_level,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
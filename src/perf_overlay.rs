@@ -0,0 +1,415 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    time::Duration,
+};
+
+use bevy::{
+    app::AppExit,
+    diagnostic::{
+        Diagnostic, DiagnosticId, Diagnostics, DiagnosticsStore, EntityCountDiagnosticsPlugin,
+        FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+    },
+    prelude::*,
+    utils::Instant,
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+
+const TOGGLE_KEY: KeyCode = KeyCode::F3;
+
+const DEFAULT_LOG_PATH: &str = "perf.csv";
+
+/// How many frames the frame-time graph shows.
+const GRAPH_SAMPLES: usize = 120;
+
+/// The frame time, in milliseconds, that reaches the top of the graph.
+const GRAPH_MAX_FRAME_TIME: f32 = 50.0;
+
+const GRAPH_HEIGHT: f32 = 60.0;
+
+/// Frames that take longer than this many milliseconds are drawn in red.
+const SLOW_FRAME_TIME: f32 = 1000.0 / 30.0;
+
+pub const PHYSICS_STEP_TIME: DiagnosticId =
+    DiagnosticId::from_u128(262711862917366530846349582711342398611);
+pub const RIGID_BODY_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(121385870958431358283011596813542717830);
+pub const ACTIVE_RIGID_BODY_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(307498917262713400431264466094219843772);
+pub const COLLIDER_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(184921009287466125790532127834190126437);
+pub const SHADOW_CASTING_LIGHT_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(94136021852305987014838410985924763129);
+/// Point lights that haven't been turned off, e.g. for being far away.
+pub const ACTIVE_LIGHT_COUNT: DiagnosticId =
+    DiagnosticId::from_u128(229801749161376014542983318806655392144);
+
+/// The diagnostics shown in the overlay and logged to CSV, in column order.
+const COLUMNS: [(DiagnosticId, &str); 9] = [
+    (FrameTimeDiagnosticsPlugin::FPS, "fps"),
+    (FrameTimeDiagnosticsPlugin::FRAME_TIME, "frame_time_ms"),
+    (PHYSICS_STEP_TIME, "physics_step_ms"),
+    (EntityCountDiagnosticsPlugin::ENTITY_COUNT, "entities"),
+    (RIGID_BODY_COUNT, "rigid_bodies"),
+    (ACTIVE_RIGID_BODY_COUNT, "active_rigid_bodies"),
+    (COLLIDER_COUNT, "colliders"),
+    (ACTIVE_LIGHT_COUNT, "active_lights"),
+    (SHADOW_CASTING_LIGHT_COUNT, "shadow_casting_lights"),
+];
+
+#[derive(Resource, Default)]
+struct PerfOverlay {
+    visible: bool,
+    /// Frame times in milliseconds, oldest first.
+    frame_times: VecDeque<f32>,
+}
+
+/// When set, a row of diagnostics is written to this file every frame.
+#[derive(Resource, Default)]
+struct PerfLog(Option<BufWriter<File>>);
+
+/// How long physics has taken this frame, which may have had any number of
+/// fixed timesteps.
+#[derive(Resource, Default)]
+struct PhysicsStepTime {
+    /// When the current step started.
+    start: Option<Instant>,
+    total: Duration,
+}
+
+#[derive(Component)]
+struct PerfOverlayRoot;
+
+#[derive(Component)]
+struct PerfOverlayText;
+
+/// One bar of the frame-time graph, numbered from the left.
+#[derive(Component)]
+struct FrameTimeBar(usize);
+
+fn setup_perf_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                z_index: ZIndex::Global(i32::MAX - 2),
+                ..default()
+            },
+            PerfOverlayRoot,
+            Name::new("PerfOverlay"),
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                PerfOverlayText,
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::FlexEnd,
+                        height: Val::Px(GRAPH_HEIGHT),
+                        margin: UiRect::top(Val::Px(4.0)),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.1).into(),
+                    ..default()
+                })
+                .with_children(|graph| {
+                    for index in 0..GRAPH_SAMPLES {
+                        graph.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(2.0),
+                                    height: Val::Px(0.0),
+                                    ..default()
+                                },
+                                background_color: Color::GREEN.into(),
+                                ..default()
+                            },
+                            FrameTimeBar(index),
+                        ));
+                    }
+                });
+        });
+}
+
+fn start_physics_step_timer(mut step_time: ResMut<PhysicsStepTime>) {
+    step_time.start = Some(Instant::now());
+}
+
+fn stop_physics_step_timer(mut step_time: ResMut<PhysicsStepTime>) {
+    if let Some(start) = step_time.start.take() {
+        step_time.total += start.elapsed();
+    }
+}
+
+fn measure_physics_step(mut diagnostics: Diagnostics, mut step_time: ResMut<PhysicsStepTime>) {
+    let total = std::mem::take(&mut step_time.total);
+    diagnostics.add_measurement(PHYSICS_STEP_TIME, || total.as_secs_f64() * 1000.0);
+}
+
+fn measure_scene_stats(
+    mut diagnostics: Diagnostics,
+    rigid_bodies: Query<(), With<RigidBody>>,
+    colliders: Query<(), With<Collider>>,
+    point_lights: Query<(&PointLight, &Visibility)>,
+    rapier_context: Res<RapierContext>,
+) {
+    diagnostics.add_measurement(RIGID_BODY_COUNT, || rigid_bodies.iter().count() as f64);
+    diagnostics.add_measurement(ACTIVE_RIGID_BODY_COUNT, || {
+        (rapier_context.islands.active_dynamic_bodies().len()
+            + rapier_context.islands.active_kinematic_bodies().len()) as f64
+    });
+    diagnostics.add_measurement(COLLIDER_COUNT, || colliders.iter().count() as f64);
+    diagnostics.add_measurement(ACTIVE_LIGHT_COUNT, || {
+        point_lights
+            .iter()
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .count() as f64
+    });
+    diagnostics.add_measurement(SHADOW_CASTING_LIGHT_COUNT, || {
+        point_lights
+            .iter()
+            .filter(|(light, visibility)| {
+                light.shadows_enabled && **visibility != Visibility::Hidden
+            })
+            .count() as f64
+    });
+}
+
+fn toggle_perf_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<PerfOverlay>) {
+    if keys.just_pressed(TOGGLE_KEY) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+fn update_perf_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    mut overlay: ResMut<PerfOverlay>,
+    mut root_query: Query<&mut Style, (With<PerfOverlayRoot>, Without<FrameTimeBar>)>,
+    mut text_query: Query<&mut Text, With<PerfOverlayText>>,
+    mut bar_query: Query<(&FrameTimeBar, &mut Style, &mut BackgroundColor)>,
+) {
+    if let Some(frame_time) = diagnostics.get_measurement(FrameTimeDiagnosticsPlugin::FRAME_TIME) {
+        overlay.frame_times.push_back(frame_time.value as f32);
+        if overlay.frame_times.len() > GRAPH_SAMPLES {
+            overlay.frame_times.pop_front();
+        }
+    }
+
+    for mut style in &mut root_query {
+        let display = if overlay.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    if !overlay.visible {
+        return;
+    }
+
+    let smoothed = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.smoothed())
+            .unwrap_or(0.0)
+    };
+    let value = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.value())
+            .unwrap_or(0.0)
+    };
+    for mut text in &mut text_query {
+        text.sections[0].value = format!(
+            "FPS: {:.0}\nFrame time: {:.2} ms\nPhysics step: {:.2} ms\nEntities: {}\n\
+             Rigid bodies: {} ({} active)\nColliders: {}\n\
+             Point lights: {} ({} casting shadows)",
+            smoothed(FrameTimeDiagnosticsPlugin::FPS),
+            smoothed(FrameTimeDiagnosticsPlugin::FRAME_TIME),
+            smoothed(PHYSICS_STEP_TIME),
+            value(EntityCountDiagnosticsPlugin::ENTITY_COUNT),
+            value(RIGID_BODY_COUNT),
+            value(ACTIVE_RIGID_BODY_COUNT),
+            value(COLLIDER_COUNT),
+            value(ACTIVE_LIGHT_COUNT),
+            value(SHADOW_CASTING_LIGHT_COUNT),
+        );
+    }
+
+    // The newest sample is on the right.
+    let offset = GRAPH_SAMPLES - overlay.frame_times.len();
+    for (bar, mut style, mut background) in &mut bar_query {
+        let frame_time = bar
+            .0
+            .checked_sub(offset)
+            .map_or(0.0, |index| overlay.frame_times[index]);
+        style.height = Val::Px((frame_time / GRAPH_MAX_FRAME_TIME).min(1.0) * GRAPH_HEIGHT);
+        background.0 = if frame_time > SLOW_FRAME_TIME {
+            Color::RED
+        } else {
+            Color::GREEN
+        };
+    }
+}
+
+fn write_perf_log(
+    time: Res<Time>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut log: ResMut<PerfLog>,
+    mut exit: EventReader<AppExit>,
+) {
+    let exiting = exit.iter().next().is_some();
+    let Some(writer) = log.0.as_mut() else {
+        return;
+    };
+    let mut row = format!("{:.4}", time.elapsed_seconds_f64());
+    for (id, _) in COLUMNS {
+        row.push(',');
+        if let Some(measurement) = diagnostics.get_measurement(id) {
+            row.push_str(&measurement.value.to_string());
+        }
+    }
+    // The app may not be dropped when it exits, so we flush the last rows
+    // ourselves.
+    let mut result = writeln!(writer, "{}", row);
+    if exiting {
+        result = result.and_then(|()| writer.flush());
+    }
+    if let Err(error) = result {
+        warn!("Failed to write performance log, stopping: {}", error);
+        log.0 = None;
+    }
+}
+
+fn start_perf_log(world: &mut World, path: &str) -> Result<String, String> {
+    let file =
+        File::create(path).map_err(|error| format!("Couldn't create '{}': {}", path, error))?;
+    let mut writer = BufWriter::new(file);
+    let header: Vec<&str> = COLUMNS.iter().map(|(_, name)| *name).collect();
+    writeln!(writer, "time,{}", header.join(","))
+        .map_err(|error| format!("Couldn't write to '{}': {}", path, error))?;
+    // Replacing an existing log flushes and closes it.
+    world.resource_mut::<PerfLog>().0 = Some(writer);
+    Ok(format!("Logging performance to '{}'.", path))
+}
+
+fn stop_perf_log(world: &mut World) -> Result<String, String> {
+    let Some(mut writer) = world.resource_mut::<PerfLog>().0.take() else {
+        return Err("Not logging performance.".to_string());
+    };
+    writer
+        .flush()
+        .map_err(|error| format!("Couldn't flush performance log: {}", error))?;
+    Ok("Stopped logging performance.".to_string())
+}
+
+fn perf_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    match args {
+        ["overlay"] => {
+            let mut overlay = world.resource_mut::<PerfOverlay>();
+            overlay.visible = !overlay.visible;
+            Ok(if overlay.visible {
+                "Performance overlay shown.".to_string()
+            } else {
+                "Performance overlay hidden.".to_string()
+            })
+        }
+        ["log"] => start_perf_log(world, DEFAULT_LOG_PATH),
+        ["log", path] => start_perf_log(world, path),
+        ["stop"] => stop_perf_log(world),
+        _ => Err("Expected 'overlay', 'log [file]' or 'stop'.".to_string()),
+    }
+}
+
+/// Measures performance, which `F3` shows in an overlay along with a graph of
+/// recent frame times, and which can be logged to a CSV file every frame.
+#[derive(Default)]
+pub struct PerfOverlayPlugin {
+    /// Where to log performance from the start, if anywhere. It can also be
+    /// started and stopped with the console's `perf` command.
+    pub log_path: Option<String>,
+}
+
+impl Plugin for PerfOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((FrameTimeDiagnosticsPlugin, EntityCountDiagnosticsPlugin))
+            .register_diagnostic(
+                Diagnostic::new(PHYSICS_STEP_TIME, "physics_step_time", 20).with_suffix("ms"),
+            )
+            .register_diagnostic(Diagnostic::new(RIGID_BODY_COUNT, "rigid_body_count", 1))
+            .register_diagnostic(Diagnostic::new(
+                ACTIVE_RIGID_BODY_COUNT,
+                "active_rigid_body_count",
+                1,
+            ))
+            .register_diagnostic(Diagnostic::new(COLLIDER_COUNT, "collider_count", 1))
+            .register_diagnostic(Diagnostic::new(ACTIVE_LIGHT_COUNT, "active_light_count", 1))
+            .register_diagnostic(Diagnostic::new(
+                SHADOW_CASTING_LIGHT_COUNT,
+                "shadow_casting_light_count",
+                1,
+            ))
+            .init_resource::<PerfOverlay>()
+            .init_resource::<PerfLog>()
+            .init_resource::<PhysicsStepTime>()
+            .add_systems(Startup, setup_perf_overlay)
+            .add_systems(
+                FixedUpdate,
+                (
+                    start_physics_step_timer
+                        .after(PhysicsSet::SyncBackendFlush)
+                        .before(PhysicsSet::StepSimulation),
+                    stop_physics_step_timer
+                        .after(PhysicsSet::StepSimulation)
+                        .before(PhysicsSet::Writeback),
+                ),
+            )
+            .add_systems(
+                Update,
+                (measure_physics_step, measure_scene_stats, toggle_perf_overlay),
+            )
+            .add_systems(Last, (update_perf_overlay, write_perf_log))
+            .add_console_command(
+                ConsoleCommand::new(
+                    "perf",
+                    "perf overlay|log [file]|stop - toggle the performance overlay or log it to CSV",
+                    perf_command,
+                )
+                .with_arguments(|_| {
+                    vec!["overlay".to_string(), "log".to_string(), "stop".to_string()]
+                }),
+            );
+
+        if let Some(path) = self.log_path.clone() {
+            app.add_systems(Startup, move |world: &mut World| {
+                match start_perf_log(world, &path) {
+                    Ok(message) => info!("{}", message),
+                    Err(error) => error!("{}", error),
+                }
+            });
+        }
+    }
+}
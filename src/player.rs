@@ -2,27 +2,91 @@ use std::f32::consts::FRAC_PI_2;
 
 use bevy::{
     core_pipeline::{bloom::BloomSettings, tonemapping::Tonemapping},
-    input::mouse::MouseMotion,
+    input::{mouse::MouseMotion, InputSystem},
     prelude::*,
+    utils::HashSet,
     window::{CursorGrabMode, PrimaryWindow},
 };
 use bevy_rapier3d::prelude::*;
 
-use crate::{app_state::AppState, config::Config, debug_mode::is_in_debug_mode};
+use crate::{
+    app_state::AppState,
+    camera_effects::CameraEffects,
+    config::Config,
+    console::{AddConsoleCommand, ConsoleCommand},
+    debug_mode::is_in_debug_mode,
+    dungeon_scene::Climbable,
+    health::{Dying, Health, LastCheckpoint},
+    time_scale::{SimulationTime, FIXED_TIMESTEP_SECONDS},
+};
 
 const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
 
 #[derive(Component, Default)]
 pub struct Player {
     velocity: Vec3,
+    /// The horizontal velocity the player is walking at.
+    walk_velocity: Vec3,
     grounded: bool,
+    /// The dynamic rigid body the player is standing on, if any.
+    ground: Option<Entity>,
+    /// Whether the player is currently climbing a climbable surface.
+    climbing: bool,
+    /// Whether the player jumped off a climbable surface and hasn't yet
+    /// left it or landed, in which case we shouldn't grab back onto it.
+    detached_from_climbable: bool,
+    sprinting: bool,
+    /// Whether jump was pressed since the last fixed timestep.
+    jump_requested: bool,
+    /// Whether force push was used since the last fixed timestep.
+    force_push_requested: bool,
+    /// Mouse motion that hasn't turned the camera yet, either because it came
+    /// since the last fixed timestep or because smoothing is holding it back.
+    pending_look: Vec2,
+    /// Where we were before the last fixed timestep, if we moved normally
+    /// rather than e.g. respawning.
+    previous_translation: Option<Vec3>,
+    /// How far the camera is from where it'd be if it followed us exactly, so
+    /// that it moves smoothly between fixed timesteps.
+    camera_offset: Vec3,
+}
+
+impl Player {
+    pub fn walk_velocity(&self) -> Vec3 {
+        self.walk_velocity
+    }
+
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.sprinting
+    }
+
+    pub fn camera_offset(&self) -> Vec3 {
+        self.camera_offset
+    }
 }
 
 #[derive(Event)]
 pub struct PlayerMovement;
 
-fn player_spawn_transform(config: &Config) -> Transform {
-    let mut position = config.spawn_position;
+/// Sent when the player uses force push, whether or not it hits anything.
+#[derive(Event)]
+pub struct PlayerForcePush {
+    /// The dynamic body that was pushed, if any.
+    pub target: Option<Entity>,
+}
+
+/// Sent when the player lands on the ground after falling or jumping.
+#[derive(Event)]
+pub struct PlayerLanded {
+    /// The downward speed the player landed at, in meters per second.
+    pub velocity: f32,
+}
+
+fn player_spawn_transform(config: &Config, mut position: Vec3) -> Transform {
     position.y += config.player_capsule_radius;
     Transform::from_translation(position)
 }
@@ -41,10 +105,16 @@ fn setup_player(mut commands: Commands, config: Res<Config>) {
                     ..default()
                 },
                 transform: player_camera_spawn_transform(&config),
+                projection: PerspectiveProjection {
+                    fov: config.camera_fov.to_radians(),
+                    ..default()
+                }
+                .into(),
                 tonemapping: Tonemapping::TonyMcMapface,
                 ..default()
             },
             BloomSettings::default(),
+            CameraEffects::default(),
         ))
         .id();
     let player_capsule = commands
@@ -55,104 +125,180 @@ fn setup_player(mut commands: Commands, config: Res<Config>) {
                 Vec3::new(0.0, config.player_capsule_cylinder_height, 0.0),
                 config.player_capsule_radius,
             ),
-            TransformBundle::from(player_spawn_transform(&config)),
+            TransformBundle::from(player_spawn_transform(&config, config.spawn_position)),
             KinematicCharacterController {
                 up: Vec3::Y,
+                autostep: Some(CharacterAutostep {
+                    max_height: CharacterLength::Absolute(config.player_autostep_max_height),
+                    min_width: CharacterLength::Absolute(config.player_autostep_min_width),
+                    include_dynamic_bodies: false,
+                }),
+                max_slope_climb_angle: config.player_max_slope_climb_angle.to_radians(),
+                min_slope_slide_angle: config.player_min_slope_slide_angle.to_radians(),
+                snap_to_ground: Some(CharacterLength::Absolute(
+                    config.player_snap_to_ground_distance,
+                )),
+                // We push dynamic bodies ourselves in `player_push_rigid_bodies`.
+                apply_impulse_to_dynamic_bodies: false,
+                // Otherwise we wouldn't be able to walk into climbable volumes.
+                filter_flags: QueryFilterFlags::EXCLUDE_SENSORS,
                 ..default()
             },
             Player::default(),
+            Health::new(config.player_max_health),
             Name::new("Player"),
         ))
         .id();
     commands.entity(player_capsule).push_children(&[camera]);
 }
 
+/// Where the player is looking from and which way, given their camera's local
+/// and global transforms, for aiming at things.
+pub(crate) fn look_ray(transform: &Transform, global_transform: &GlobalTransform) -> (Vec3, Vec3) {
+    (global_transform.translation(), -transform.local_z())
+}
+
 fn player_force_push(
     mut commands: Commands,
-    buttons: Res<Input<MouseButton>>,
     rapier_context: Res<RapierContext>,
-    player_query: Query<Entity, With<Player>>,
-    camera_query: Query<(&Parent, &Transform, &GlobalTransform), With<Camera>>,
+    mut player_query: Query<(Entity, &mut Player, &Transform)>,
+    camera_query: Query<(&Parent, &Transform), With<Camera>>,
     entity_names: Query<&Name>,
     rigid_bodies: Query<&RigidBody>,
+    mut force_pushes: EventWriter<PlayerForcePush>,
     config: Res<Config>,
 ) {
-    if buttons.just_pressed(MouseButton::Right) {
-        for (parent, transform, global_transform) in &camera_query {
-            let Ok(player) = player_query.get(parent.get()) else {
-                warn!("Parent of camera has no kinematic character controller!");
-                continue;
-            };
-            let ray_pos = global_transform.translation();
-            let ray_dir: Vec3 = -transform.local_z();
-            let filter = QueryFilter::new();
-            if let Some((entity, toi)) = rapier_context.cast_ray(
+    for (parent, transform) in &camera_query {
+        let Ok((entity, mut player, player_transform)) = player_query.get_mut(parent.get()) else {
+            warn!("Parent of camera has no kinematic character controller!");
+            continue;
+        };
+        if std::mem::take(&mut player.force_push_requested) {
+            // Aim from where the camera would be without its effects, like head
+            // bob, which follow the frame rate rather than the simulation.
+            let ray_pos = player_transform.translation + Vec3::Y * config.player_camera_height;
+            let ray_dir = -transform.local_z();
+            let filter = QueryFilter::new().exclude_sensors();
+            let mut target = None;
+            if let Some((hit, toi)) = rapier_context.cast_ray(
                 ray_pos,
                 ray_dir,
                 config.player_force_push_max_distance,
                 true,
-                filter.exclude_rigid_body(player),
+                filter.exclude_rigid_body(entity),
             ) {
-                let Ok(name) = entity_names.get(entity) else {
-                    continue;
-                };
-
-                info!("HIT '{}' toi={}", name, toi);
-
-                if let Ok(rigid_body) = rigid_bodies.get(entity) {
-                    if *rigid_body == RigidBody::Dynamic {
-                        let impulse = ExternalImpulse {
-                            impulse: -ray_dir * config.player_force_push_velocity,
-                            torque_impulse: Vec3::ZERO,
-                        };
-                        commands.entity(entity).insert(impulse);
-                    }
+                if let Ok(name) = entity_names.get(hit) {
+                    info!("HIT '{}' toi={}", name, toi);
+                }
+
+                if rigid_bodies.get(hit) == Ok(&RigidBody::Dynamic) {
+                    let impulse = ExternalImpulse {
+                        impulse: -ray_dir * config.player_force_push_velocity,
+                        torque_impulse: Vec3::ZERO,
+                    };
+                    commands.entity(hit).insert(impulse);
+                    target = Some(hit);
                 }
             }
+            force_pushes.send(PlayerForcePush { target });
         }
     }
 }
 
+/// Remembers jump presses, force pushes and mouse motion until the next fixed
+/// timestep, since there might not be one this frame. Handling them there
+/// means they affect the simulation the same way whatever the frame rate.
+fn buffer_player_input(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion_events: EventReader<MouseMotion>,
+    mut player_query: Query<&mut Player>,
+) {
+    let motion: Vec2 = motion_events.iter().map(|event| event.delta).sum();
+    for mut player in &mut player_query {
+        if keys.just_pressed(KeyCode::Space) {
+            player.jump_requested = true;
+        }
+        if buttons.just_pressed(MouseButton::Right) {
+            player.force_push_requested = true;
+        }
+        player.pending_look += motion;
+    }
+}
+
+fn record_player_translation(mut player_query: Query<(&mut Player, &Transform)>) {
+    for (mut player, transform) in &mut player_query {
+        player.previous_translation = Some(transform.translation);
+    }
+}
+
 fn player_movement(
-    time: Res<Time>,
     keys: Res<Input<KeyCode>>,
-    mut player_query: Query<(&mut KinematicCharacterController, &mut Player)>,
+    mut player_query: Query<(
+        &mut KinematicCharacterController,
+        &mut Player,
+        Option<&Dying>,
+    )>,
     camera_query: Query<(&Parent, &Transform), With<Camera>>,
+    velocities: Query<&Velocity>,
     mut player_movement: EventWriter<PlayerMovement>,
     config: Res<Config>,
 ) {
+    let dt = FIXED_TIMESTEP_SECONDS as f32;
     for (parent, transform) in &camera_query {
-        let Ok((mut controller, mut player)) = player_query.get_mut(parent.get()) else {
+        let Ok((mut controller, mut player, dying)) = player_query.get_mut(parent.get()) else {
             warn!("Parent of camera has no kinematic character controller!");
             continue;
         };
+        // The dead can't move, but they can still fall.
+        let can_move = dying.is_none();
         // This is mostly taken from bevy_flycam's movement code.
         let mut velocity = Vec3::ZERO;
         let local_z = transform.local_z();
         let forward = -Vec3::new(local_z.x, 0., local_z.z);
         let right = Vec3::new(local_z.z, 0., -local_z.x);
 
-        if keys.just_pressed(KeyCode::Space) && player.grounded {
+        let jump_requested = std::mem::take(&mut player.jump_requested);
+        if can_move && jump_requested && (player.grounded || player.climbing) {
             player.velocity = Vec3::new(0.0, config.jump_velocity, 0.0);
             player.grounded = false;
-        } else if player.grounded && player.velocity.y < 0.0 {
-            // Don't set this to 0 or the physics engine will constantly flip us between
-            // being grounded and not-grounded each frame.
-            player.velocity.y = -0.1;
-        } else if !player.grounded {
-            player.velocity.y -= config.gravity * time.delta_seconds();
+            if player.climbing {
+                player.climbing = false;
+                player.detached_from_climbable = true;
+            }
+        } else if player.climbing {
+            // Gravity is suspended while climbing.
+            player.velocity = Vec3::ZERO;
+        } else {
+            if player.grounded && player.velocity.y < 0.0 {
+                player.velocity.y = 0.0;
+            }
+            // We apply gravity even when grounded, so that the character controller
+            // always detects the floor beneath us and can snap us down onto slopes
+            // and stairs instead of letting us bounce off of them.
+            player.velocity.y -= config.gravity * dt;
         }
 
-        for key in keys.get_pressed() {
+        let mut climb = 0.0;
+
+        for key in keys.get_pressed().filter(|_| can_move) {
             match key {
                 KeyCode::W => {
+                    // When climbing, we still move forward so that we can step off
+                    // onto whatever is at the top of the climbable surface.
                     velocity += forward;
+                    climb += 1.0;
                 }
                 KeyCode::A => {
                     velocity -= right;
                 }
                 KeyCode::S => {
-                    velocity -= forward;
+                    // When climbing, we only move down, rather than backing away
+                    // from the climbable surface and falling off it.
+                    if !player.climbing {
+                        velocity -= forward;
+                    }
+                    climb -= 1.0;
                 }
                 KeyCode::D => {
                     velocity += right;
@@ -163,76 +309,304 @@ fn player_movement(
 
         velocity = velocity.normalize_or_zero();
 
-        if velocity != Vec3::ZERO {
+        player.sprinting = can_move
+            && !player.climbing
+            && velocity != Vec3::ZERO
+            && keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+        if !player.climbing {
+            climb = 0.0;
+        }
+
+        if velocity != Vec3::ZERO || climb != 0.0 {
             player_movement.send(PlayerMovement);
         }
 
-        let desired_translation = velocity * time.delta_seconds() * config.player_speed
-            + player.velocity * time.delta_seconds();
+        player.walk_velocity = velocity * config.player_speed;
+        if player.sprinting {
+            player.walk_velocity *= config.player_sprint_multiplier;
+        }
+
+        let mut desired_translation = player.walk_velocity * dt + player.velocity * dt;
+
+        desired_translation.y += climb * config.player_climb_speed * dt;
+
+        if let Some(ground_velocity) = player.ground.and_then(|ground| velocities.get(ground).ok())
+        {
+            // Move along with whatever we're standing on, so we ride it rather than
+            // jittering on top of it as it moves out from under us.
+            desired_translation += ground_velocity.linvel * dt;
+        }
 
         controller.translation = Some(desired_translation);
     }
 }
 
 fn update_player_after_physics(
-    mut query: Query<(&mut Player, &KinematicCharacterControllerOutput)>,
+    mut query: Query<(
+        Entity,
+        &mut Player,
+        &mut Health,
+        &Transform,
+        &KinematicCharacterControllerOutput,
+    )>,
+    rapier_context: Res<RapierContext>,
+    rigid_bodies: Query<&RigidBody>,
+    mut player_landed: EventWriter<PlayerLanded>,
+    config: Res<Config>,
 ) {
-    for (mut player, output) in query.iter_mut() {
+    for (entity, mut player, mut health, transform, output) in query.iter_mut() {
+        if output.grounded && !player.grounded {
+            let landing_velocity = -player.velocity.y;
+            if landing_velocity > 0.0 {
+                player_landed.send(PlayerLanded {
+                    velocity: landing_velocity,
+                });
+            }
+            let excess_velocity = landing_velocity - config.fall_damage_min_velocity;
+            if excess_velocity > 0.0 {
+                let damage = excess_velocity * config.fall_damage_per_velocity;
+                info!(
+                    "Landed at {} m/s, taking {} fall damage.",
+                    landing_velocity, damage
+                );
+                health.damage(damage);
+            }
+        }
         player.grounded = output.grounded;
+        player.ground = None;
+        if !player.grounded {
+            continue;
+        }
+        // The bottom hemisphere of our capsule is centered at our origin, so anything
+        // we're standing on will be just beyond its radius.
+        let max_toi = config.player_capsule_radius + config.player_snap_to_ground_distance;
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .exclude_sensors();
+        if let Some((ground, _toi)) =
+            rapier_context.cast_ray(transform.translation, -Vec3::Y, max_toi, true, filter)
+        {
+            if rigid_bodies.get(ground) == Ok(&RigidBody::Dynamic) {
+                player.ground = Some(ground);
+            }
+        }
+    }
+}
+
+fn update_player_climbing(
+    mut query: Query<(Entity, &mut Player, &Transform, &Collider)>,
+    climbables: Query<(), With<Climbable>>,
+    rapier_context: Res<RapierContext>,
+) {
+    for (entity, mut player, transform, collider) in query.iter_mut() {
+        let is_climbable = |entity| climbables.contains(entity);
+        let filter = QueryFilter::new()
+            .exclude_rigid_body(entity)
+            .predicate(&is_climbable);
+        let mut overlapping = false;
+        rapier_context.intersections_with_shape(
+            transform.translation,
+            transform.rotation,
+            collider,
+            filter,
+            |_| {
+                overlapping = true;
+                false
+            },
+        );
+
+        if !overlapping || player.grounded {
+            player.detached_from_climbable = false;
+        }
+        player.climbing = overlapping && !player.detached_from_climbable;
+    }
+}
+
+fn player_push_rigid_bodies(
+    mut commands: Commands,
+    player_query: Query<(&Player, &KinematicCharacterControllerOutput)>,
+    rigid_bodies: Query<(&RigidBody, &ReadMassProperties, Option<&Velocity>)>,
+    config: Res<Config>,
+) {
+    for (player, output) in &player_query {
+        let speed = player.walk_velocity.length();
+        if speed == 0.0 {
+            continue;
+        }
+        let direction = player.walk_velocity / speed;
+        let mut pushed = HashSet::new();
+        for collision in &output.collisions {
+            // Don't push whatever we're standing on, and only push each body once,
+            // since we can collide with the same body multiple times per move.
+            if player.ground == Some(collision.entity) || !pushed.insert(collision.entity) {
+                continue;
+            }
+            let Ok((rigid_body, mass_properties, velocity)) = rigid_bodies.get(collision.entity)
+            else {
+                continue;
+            };
+            let mass = mass_properties.0.mass;
+            if *rigid_body != RigidBody::Dynamic || mass <= 0.0 {
+                continue;
+            }
+            // We push every body equally hard, so lighter ones speed up more,
+            // though never past us.
+            let push = speed
+                * config.player_push_max_mass
+                * config.player_push_strength
+                * FIXED_TIMESTEP_SECONDS as f32;
+            let body_speed = velocity.map_or(0.0, |velocity| velocity.linvel.dot(direction));
+            let max_push = (speed - body_speed).max(0.0) * mass;
+            let impulse = ExternalImpulse {
+                impulse: direction * push.min(max_push),
+                torque_impulse: Vec3::ZERO,
+            };
+            commands.entity(collision.entity).insert(impulse);
+        }
+    }
+}
+
+/// Works out where the camera should be between the last fixed timestep and the
+/// next one.
+pub(crate) fn interpolate_player_camera(
+    fixed_time: Res<FixedTime>,
+    sim_time: Res<SimulationTime>,
+    mut player_query: Query<(&mut Player, &Transform)>,
+) {
+    let fraction = sim_time.step_fraction(&fixed_time);
+    for (mut player, transform) in &mut player_query {
+        let previous = player.previous_translation.unwrap_or(transform.translation);
+        player.camera_offset = (previous - transform.translation) * (1.0 - fraction);
     }
 }
 
 fn player_look(
     primary_window: Query<&mut Window, With<PrimaryWindow>>,
-    mut motion_events: EventReader<MouseMotion>,
-    mut query: Query<&mut Transform, With<Camera3d>>,
+    mut player_query: Query<&mut Player>,
+    mut camera_query: Query<(&Parent, &mut Transform), With<Camera3d>>,
     config: Res<Config>,
 ) {
     let Ok(window) = primary_window.get_single() else {
         warn!("No primary window when trying to mouselook!");
         return;
     };
-    for mut transform in &mut query {
-        for event in motion_events.iter() {
-            // This is mostly taken from bevy_flycam's mouselook code.
-            let (mut yaw, mut pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
-            // Using smallest of height or width ensures equal vertical and horizontal sensitivity.
-            let window_scale = window.height().min(window.width());
-            pitch -= (config.mouse_sensitivity * event.delta.y * window_scale).to_radians();
-            yaw -= (config.mouse_sensitivity * event.delta.x * window_scale).to_radians();
-
-            pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
-
-            // Order is important to prevent unintended roll.
-            transform.rotation =
-                Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+    for (parent, mut transform) in &mut camera_query {
+        let Ok(mut player) = player_query.get_mut(parent.get()) else {
+            warn!("Parent of camera has no player!");
+            continue;
+        };
+        // When smoothing, we only apply some of the mouse motion this step,
+        // leaving the rest for later steps.
+        let motion = if config.mouse_smoothing_enabled && config.mouse_smoothing > 0.0 {
+            let dt = FIXED_TIMESTEP_SECONDS as f32;
+            player.pending_look * (1.0 - (-dt / config.mouse_smoothing).exp())
+        } else {
+            player.pending_look
+        };
+        player.pending_look -= motion;
+        if motion == Vec2::ZERO {
+            continue;
         }
+        // This is mostly taken from bevy_flycam's mouselook code.
+        let (mut yaw, mut pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        // Using smallest of height or width ensures equal vertical and horizontal sensitivity.
+        let window_scale = window.height().min(window.width());
+        pitch -= (config.mouse_sensitivity * motion.y * window_scale).to_radians();
+        yaw -= (config.mouse_sensitivity * motion.x * window_scale).to_radians();
+
+        pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+
+        // Order is important to prevent unintended roll.
+        transform.rotation =
+            Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
     }
 }
 
 fn maybe_respawn_player(
-    mut player_query: Query<(&mut Player, &mut Transform), Without<Camera>>,
+    mut commands: Commands,
+    mut player_query: Query<
+        (
+            Entity,
+            &mut Player,
+            &mut Health,
+            &mut Transform,
+            Option<&Dying>,
+        ),
+        Without<Camera>,
+    >,
     mut camera_query: Query<(&Parent, &mut Transform), With<Camera>>,
+    last_checkpoint: Res<LastCheckpoint>,
     config: Res<Config>,
 ) {
     for (parent, mut camera_transform) in &mut camera_query {
-        let Ok((mut player, mut player_transform)) = player_query.get_mut(parent.get()) else {
+        let Ok((entity, mut player, mut health, mut player_transform, dying)) =
+            player_query.get_mut(parent.get())
+        else {
             warn!("Parent of camera has no kinematic character controller!");
             continue;
         };
 
         if player_transform.translation.y < config.fall_off_level_y {
-            // Really we are teleporting the player back to their spawn position,
-            // rather than respawning them. Also, this could run into weird edge
-            // cases, e.g. if the player pushed a crate over their spawn position,
-            // but this is better than dooming the player to an infinite fall.
+            // Falling off the level is fatal, otherwise the player would be
+            // doomed to an infinite fall.
+            health.current = 0.0;
+        }
+
+        let Some(dying) = dying else {
+            continue;
+        };
+
+        if dying.0.finished() {
+            // This could run into weird edge cases, e.g. if the player pushed a
+            // crate over the checkpoint, but it's good enough for now.
             *player = default();
-            *player_transform = player_spawn_transform(&config);
+            health.restore();
+            *player_transform = player_spawn_transform(&config, last_checkpoint.0);
             *camera_transform = player_camera_spawn_transform(&config);
+            commands.entity(entity).remove::<Dying>();
         }
     }
 }
 
+fn despawn_player(mut commands: Commands, query: Query<Entity, With<Player>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn teleport_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let [x, y, z] = args else {
+        return Err("Expected x, y and z coordinates.".to_string());
+    };
+    let parse = |value: &str| {
+        value
+            .parse::<f32>()
+            .map_err(|_| format!("Invalid coordinate '{}'.", value))
+    };
+    let position = Vec3::new(parse(x)?, parse(y)?, parse(z)?);
+    let config = world.resource::<Config>().clone();
+    let mut query = world.query::<(&mut Player, &mut Transform)>();
+    let Ok((mut player, mut transform)) = query.get_single_mut(world) else {
+        return Err("There is no player to teleport.".to_string());
+    };
+    *player = default();
+    *transform = player_spawn_transform(&config, position);
+    Ok(format!("Teleported to {}.", position))
+}
+
+fn god_command(world: &mut World, _args: &[&str]) -> Result<String, String> {
+    let mut query = world.query_filtered::<&mut Health, With<Player>>();
+    let Ok(mut health) = query.get_single_mut(world) else {
+        return Err("There is no player.".to_string());
+    };
+    health.invulnerable = !health.invulnerable;
+    Ok(format!(
+        "God mode {}.",
+        if health.invulnerable { "on" } else { "off" }
+    ))
+}
+
 fn grab_cursor(mut primary_window: Query<&mut Window, With<PrimaryWindow>>) {
     if let Ok(mut window) = primary_window.get_single_mut() {
         window.cursor.grab_mode = CursorGrabMode::Confined;
@@ -247,18 +621,51 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(AppState::SettingUpScene), setup_player)
+            .add_systems(OnExit(AppState::InGame), despawn_player)
+            .add_console_command(ConsoleCommand::new(
+                "teleport",
+                "teleport <x> <y> <z> - move the player",
+                teleport_command,
+            ))
+            .add_console_command(ConsoleCommand::new(
+                "god",
+                "god - toggle invulnerability",
+                god_command,
+            ))
             .add_systems(Startup, grab_cursor)
             .add_systems(
-                Update,
+                PreUpdate,
+                buffer_player_input
+                    .after(InputSystem)
+                    .run_if(in_state(AppState::InGame).and_then(not(is_in_debug_mode))),
+            )
+            .add_systems(
+                FixedUpdate,
                 (
-                    maybe_respawn_player,
-                    player_movement.run_if(not(is_in_debug_mode)),
-                    player_look.run_if(not(is_in_debug_mode)),
-                    player_force_push.run_if(not(is_in_debug_mode)),
-                    update_player_after_physics,
+                    (
+                        record_player_translation,
+                        // Otherwise we could move with the velocity we had before
+                        // respawning, and end up inside the floor.
+                        maybe_respawn_player,
+                        update_player_climbing,
+                        (player_look, player_force_push, player_movement)
+                            .chain()
+                            .run_if(not(is_in_debug_mode)),
+                    )
+                        .chain()
+                        .before(PhysicsSet::SyncBackend),
+                    (update_player_after_physics, player_push_rigid_bodies)
+                        .chain()
+                        .after(PhysicsSet::Writeback),
                 )
                     .run_if(in_state(AppState::InGame)),
             )
-            .add_event::<PlayerMovement>();
+            .add_systems(
+                Update,
+                interpolate_player_camera.run_if(in_state(AppState::InGame)),
+            )
+            .add_event::<PlayerMovement>()
+            .add_event::<PlayerLanded>()
+            .add_event::<PlayerForcePush>();
     }
 }
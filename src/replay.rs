@@ -0,0 +1,400 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use bevy::{
+    app::AppExit,
+    ecs::query::Has,
+    input::{
+        keyboard::KeyboardInput, mouse::MouseButtonInput, mouse::MouseMotion, ButtonState,
+        InputSystem,
+    },
+    prelude::*,
+    time::TimeUpdateStrategy,
+    window::PrimaryWindow,
+};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    config::{apply_config, Config},
+    dungeon_scene::CurrentLevel,
+    time_scale::FIXED_TIMESTEP_SECONDS,
+};
+
+/// The input that arrived in time for a single fixed timestep.
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct RecordedTick {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<(KeyCode, ButtonState)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buttons: Vec<(MouseButton, ButtonState)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mouse_motion: Vec<Vec2>,
+}
+
+/// Everything needed to reproduce a play session, starting from when the level
+/// has loaded. Console commands, and changes to the config after the
+/// recording starts, aren't recorded. There's a tick for every fixed timestep,
+/// so the simulation sees the same input however fast the game was running.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recording {
+    pub level: String,
+    pub config: Config,
+    /// The size of the primary window, which affects how far mouse motion turns
+    /// the camera.
+    pub window_size: Vec2,
+    pub ticks: Vec<RecordedTick>,
+    /// The `state_hash` after the last tick.
+    pub final_state_hash: Option<u64>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read '{}': {}", path, error))?;
+        serde_json::from_str(&json).map_err(|error| format!("Couldn't parse '{}': {}", path, error))
+    }
+
+    fn save(&self, path: &PathBuf) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|error| error.to_string())?;
+        fs::write(path, json).map_err(|error| error.to_string())
+    }
+}
+
+/// The objects whose state is hashed: every rigid body, including the player,
+/// and every camera.
+type HashedObjects<'w, 's> = Query<
+    'w,
+    's,
+    (Option<&'static Name>, &'static Transform, Has<Camera3d>),
+    Or<(With<RigidBody>, With<Camera3d>)>,
+>;
+
+/// Hashes the positions of every rigid body, including the player, and the
+/// orientation of every camera, so that two runs can be compared. Cameras'
+/// positions are left out, since effects like head bob move them with the
+/// frame rate rather than the simulation. This uses FNV-1a rather than Rust's
+/// default hasher, whose output may change between Rust releases.
+pub fn state_hash<'a>(
+    objects: impl Iterator<Item = (Option<&'a Name>, &'a Transform, bool)>,
+) -> u64 {
+    let mut objects: Vec<(&str, [u32; 7])> = objects
+        .map(|(name, transform, is_camera)| {
+            let t = if is_camera {
+                Vec3::ZERO
+            } else {
+                transform.translation
+            };
+            let r = transform.rotation;
+            let name = name.map_or("", |name| name.as_str());
+            (name, [t.x, t.y, t.z, r.x, r.y, r.z, r.w].map(f32::to_bits))
+        })
+        .collect();
+    // Query order isn't guaranteed, so make sure we always hash in the same order.
+    objects.sort();
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    for (name, bits) in objects {
+        write(name.as_bytes());
+        for value in bits {
+            write(&value.to_le_bytes());
+        }
+    }
+    hash
+}
+
+/// Computes the `state_hash` of a world, e.g. at the end of a test.
+pub fn world_state_hash(world: &mut World) -> u64 {
+    let mut query = world.query_filtered::<
+        (Option<&Name>, &Transform, Has<Camera3d>),
+        Or<(With<RigidBody>, With<Camera3d>)>,
+    >();
+    state_hash(query.iter(world))
+}
+
+/// Records input to a file while in game. It's saved when the app exits or the
+/// level is unloaded.
+#[derive(Resource)]
+struct InputRecorder {
+    path: PathBuf,
+    recording: Option<Recording>,
+    /// The input that has arrived since the last fixed timestep.
+    pending: RecordedTick,
+    saved: bool,
+}
+
+/// Plays back a recording in place of real input.
+#[derive(Resource)]
+pub struct InputReplay {
+    recording: Recording,
+    /// How many fixed timesteps have run in game, which is also the index of
+    /// the tick whose input the next one gets.
+    next_tick: usize,
+    /// The time update strategy to return to once the replay is over.
+    previous_time_update_strategy: Option<TimeUpdateStrategy>,
+    final_state_hash: Option<u64>,
+    exit_when_finished: bool,
+}
+
+impl InputReplay {
+    pub fn is_finished(&self) -> bool {
+        self.final_state_hash.is_some()
+    }
+
+    /// The `state_hash` after the last tick, once the replay is finished.
+    pub fn final_state_hash(&self) -> Option<u64> {
+        self.final_state_hash
+    }
+}
+
+/// Gathers the input that arrives each frame until the next fixed timestep
+/// takes it. We always read events, so that those sent before recording
+/// started aren't included in the first tick.
+fn collect_input(
+    state: Res<State<AppState>>,
+    mut recorder: ResMut<InputRecorder>,
+    mut keys: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut mouse_motion: EventReader<MouseMotion>,
+) {
+    let input = RecordedTick {
+        keys: keys
+            .iter()
+            .filter_map(|event| Some((event.key_code?, event.state)))
+            .collect(),
+        buttons: buttons
+            .iter()
+            .map(|event| (event.button, event.state))
+            .collect(),
+        mouse_motion: mouse_motion.iter().map(|event| event.delta).collect(),
+    };
+    if recorder.saved || *state.get() != AppState::InGame {
+        return;
+    }
+    let pending = &mut recorder.pending;
+    pending.keys.extend(input.keys);
+    pending.buttons.extend(input.buttons);
+    pending.mouse_motion.extend(input.mouse_motion);
+}
+
+fn record_tick(
+    mut recorder: ResMut<InputRecorder>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    level: Res<CurrentLevel>,
+    config: Res<Config>,
+) {
+    if recorder.saved {
+        return;
+    }
+    let mut tick = std::mem::take(&mut recorder.pending);
+    let recording = recorder.recording.get_or_insert_with(|| {
+        info!("Recording input...");
+        // A replay can't feed in input before the game's first tick, so this
+        // makes sure there's none to miss.
+        tick = RecordedTick::default();
+        Recording {
+            level: level.0.clone(),
+            config: config.clone(),
+            window_size: window_query.get_single().map_or(Vec2::ZERO, |window| {
+                Vec2::new(window.width(), window.height())
+            }),
+            ticks: vec![],
+            final_state_hash: None,
+        }
+    });
+    recording.ticks.push(tick);
+}
+
+fn save_recording(
+    state: Res<State<AppState>>,
+    mut recorder: ResMut<InputRecorder>,
+    mut exit: EventReader<AppExit>,
+    objects: HashedObjects,
+) {
+    let exiting = exit.iter().next().is_some();
+    if recorder.saved {
+        return;
+    }
+    let in_game = *state.get() == AppState::InGame;
+    let InputRecorder {
+        path,
+        recording,
+        saved,
+        ..
+    } = recorder.as_mut();
+    let Some(recording) = recording else {
+        return;
+    };
+    if in_game {
+        recording.final_state_hash = Some(state_hash(objects.iter()));
+    }
+    if exiting || !in_game {
+        match recording.save(path) {
+            Ok(()) => info!(
+                "Saved {} ticks of input to '{}'.",
+                recording.ticks.len(),
+                path.display()
+            ),
+            Err(error) => error!("Failed to save input recording: {}", error),
+        }
+        *saved = true;
+    }
+}
+
+/// Sets up the level and window as they were, and advances time by exactly one
+/// fixed timestep each frame, so that every frame plays a single tick.
+fn start_replay(
+    mut replay: ResMut<InputReplay>,
+    mut level: ResMut<CurrentLevel>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    info!(
+        "Replaying {} ticks of input...",
+        replay.recording.ticks.len()
+    );
+    level.0 = replay.recording.level.clone();
+    replay.previous_time_update_strategy = Some(std::mem::replace(
+        time_update_strategy.as_mut(),
+        TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(FIXED_TIMESTEP_SECONDS)),
+    ));
+    let size = replay.recording.window_size;
+    if let Ok(mut window) = window_query.get_single_mut() {
+        if size != Vec2::ZERO {
+            window.resolution.set(size.x, size.y);
+        }
+    }
+}
+
+fn apply_replay_config(replay: Res<InputReplay>, mut config: ResMut<Config>) {
+    *config = replay.recording.config.clone();
+}
+
+/// Replaces this frame's input with that of the tick the fixed timestep is
+/// about to run. The first tick runs on the frame that the game starts on,
+/// before this does, which is fine since it's always recorded empty.
+fn play_input(
+    replay: Res<InputReplay>,
+    mut keys: ResMut<Events<KeyboardInput>>,
+    mut buttons: ResMut<Events<MouseButtonInput>>,
+    mut mouse_motion: ResMut<Events<MouseMotion>>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+) {
+    let Some(tick) = replay.recording.ticks.get(replay.next_tick) else {
+        return;
+    };
+    keys.clear();
+    buttons.clear();
+    mouse_motion.clear();
+    let window = window_query.get_single().unwrap_or(Entity::PLACEHOLDER);
+    for &(key_code, state) in &tick.keys {
+        keys.send(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+            window,
+        });
+    }
+    for &(button, state) in &tick.buttons {
+        buttons.send(MouseButtonInput {
+            button,
+            state,
+            window,
+        });
+    }
+    for &delta in &tick.mouse_motion {
+        mouse_motion.send(MouseMotion { delta });
+    }
+}
+
+fn advance_replay(mut replay: ResMut<InputReplay>) {
+    replay.next_tick += 1;
+}
+
+fn finish_replay(
+    mut replay: ResMut<InputReplay>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+    objects: HashedObjects,
+    mut exit: EventWriter<AppExit>,
+) {
+    if replay.is_finished() || replay.next_tick < replay.recording.ticks.len() {
+        return;
+    }
+    // We're done, so go back to normal.
+    if let Some(strategy) = replay.previous_time_update_strategy.take() {
+        *time_update_strategy = strategy;
+    }
+    let hash = state_hash(objects.iter());
+    replay.final_state_hash = Some(hash);
+    match replay.recording.final_state_hash {
+        Some(expected) if expected != hash => warn!(
+            "Replay finished with state hash {:016x}, but the recording ended with {:016x}.",
+            hash, expected
+        ),
+        _ => info!("Replay finished with state hash {:016x}.", hash),
+    }
+    if replay.exit_when_finished {
+        exit.send(AppExit);
+    }
+}
+
+/// Records all input to a file, so that it can be replayed with `ReplayPlugin`.
+pub struct RecordPlugin {
+    pub path: PathBuf,
+}
+
+impl Plugin for RecordPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputRecorder {
+            path: self.path.clone(),
+            recording: None,
+            pending: RecordedTick::default(),
+            saved: false,
+        })
+        .add_systems(PreUpdate, collect_input.after(InputSystem))
+        .add_systems(FixedUpdate, record_tick.run_if(in_state(AppState::InGame)))
+        .add_systems(Last, save_recording);
+    }
+}
+
+/// Replays a recording deterministically, running one fixed timestep per frame
+/// with the input recorded for it.
+pub struct ReplayPlugin {
+    pub recording: Recording,
+    /// Whether to exit the app when the replay is over, e.g. when running headlessly.
+    pub exit_when_finished: bool,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InputReplay {
+            recording: self.recording.clone(),
+            next_tick: 0,
+            previous_time_update_strategy: None,
+            final_state_hash: None,
+            exit_when_finished: self.exit_when_finished,
+        })
+        .add_systems(Startup, start_replay)
+        .add_systems(
+            OnExit(AppState::LoadingAssets),
+            apply_replay_config.after(apply_config),
+        )
+        .add_systems(
+            PreUpdate,
+            play_input
+                .before(InputSystem)
+                .run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(
+            FixedUpdate,
+            advance_replay.run_if(in_state(AppState::InGame)),
+        )
+        .add_systems(Last, finish_replay);
+    }
+}
@@ -0,0 +1,536 @@
+use std::path::PathBuf;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    collectible::Collectible,
+    console::{AddConsoleCommand, ConsoleCommand},
+    door::Door,
+    dungeon_scene::CurrentLevel,
+    health::{Dying, Health, LastCheckpoint},
+    instructions::{spawn_centered_hud_text, Fonts},
+    inventory::{Inventory, InventoryItem},
+    objectives::LevelProgress,
+    player::Player,
+    torch::Torch,
+};
+
+/// Bumped whenever `SaveGame` changes, since older saves can't be loaded.
+const SAVE_VERSION: u32 = 1;
+
+const QUICKSAVE_SLOT: &str = "quick";
+
+/// The slots that can be chosen with `SELECT_SLOT_KEY`, and what they're
+/// called on screen. Others can only be used from the console.
+const SELECTABLE_SLOTS: [(&str, &str); 4] = [
+    (QUICKSAVE_SLOT, "Quicksave"),
+    ("slot1", "Slot 1"),
+    ("slot2", "Slot 2"),
+    ("slot3", "Slot 3"),
+];
+
+const QUICKSAVE_KEY: KeyCode = KeyCode::F5;
+
+const QUICKLOAD_KEY: KeyCode = KeyCode::F9;
+
+const SELECT_SLOT_KEY: KeyCode = KeyCode::F6;
+
+/// How long messages about saving and loading stay on screen, in seconds.
+const MESSAGE_DURATION: f32 = 3.0;
+
+/// A dynamic rigid body, e.g. a crate or barrel, which is identified by the
+/// name it was given in Blender.
+#[derive(Serialize, Deserialize)]
+struct SavedBody {
+    name: String,
+    translation: Vec3,
+    rotation: Quat,
+    linvel: Vec3,
+    angvel: Vec3,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveGame {
+    version: u32,
+    level: String,
+    player_transform: Transform,
+    camera_yaw: f32,
+    camera_pitch: f32,
+    last_checkpoint: Vec3,
+    bodies: Vec<SavedBody>,
+    /// The names of the torches that have been put out.
+    unlit_torches: Vec<String>,
+    /// The names of the items that have been picked up.
+    collected_items: Vec<String>,
+    elapsed_seconds: f32,
+    exit_reached: bool,
+    inventory: Vec<InventoryItem>,
+    /// The names of the doors that have been unlocked.
+    open_doors: Vec<String>,
+}
+
+/// A save that will be applied once its level has finished loading.
+#[derive(Resource)]
+struct PendingLoad(SaveGame);
+
+fn capture_save_game(world: &mut World) -> Result<SaveGame, String> {
+    let mut player_query = world.query_filtered::<(Entity, &Transform), With<Player>>();
+    let Ok((player, player_transform)) = player_query.get_single(world) else {
+        return Err("There is no player to save.".to_string());
+    };
+    let player_transform = *player_transform;
+
+    let mut camera_query = world.query_filtered::<(&Parent, &Transform), With<Camera3d>>();
+    let Some((_, camera_transform)) = camera_query
+        .iter(world)
+        .find(|(parent, _)| parent.get() == player)
+    else {
+        return Err("The camera isn't attached to the player.".to_string());
+    };
+    let (camera_yaw, camera_pitch, _) = camera_transform.rotation.to_euler(EulerRot::YXZ);
+
+    let mut body_query = world.query::<(&Name, &RigidBody, &Transform, &Velocity)>();
+    let mut bodies: Vec<SavedBody> = body_query
+        .iter(world)
+        .filter(|(name, rigid_body, ..)| {
+            name.contains("-rigid") && **rigid_body == RigidBody::Dynamic
+        })
+        .map(|(name, _, transform, velocity)| SavedBody {
+            name: name.to_string(),
+            translation: transform.translation,
+            rotation: transform.rotation,
+            linvel: velocity.linvel,
+            angvel: velocity.angvel,
+        })
+        .collect();
+    // Query order isn't stable, and this keeps the file easy to compare.
+    bodies.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut torch_query = world.query::<(&Name, &Torch)>();
+    let mut unlit_torches: Vec<String> = torch_query
+        .iter(world)
+        .filter(|(_, torch)| !torch.is_lit())
+        .map(|(name, _)| name.to_string())
+        .collect();
+    unlit_torches.sort();
+
+    let mut collectible_query = world.query::<(&Name, &Collectible)>();
+    let mut collected_items: Vec<String> = collectible_query
+        .iter(world)
+        .filter(|(_, collectible)| collectible.is_collected())
+        .map(|(name, _)| name.to_string())
+        .collect();
+    collected_items.sort();
+
+    let mut door_query = world.query::<(&Name, &Door)>();
+    let mut open_doors: Vec<String> = door_query
+        .iter(world)
+        .filter(|(_, door)| door.is_open())
+        .map(|(name, _)| name.to_string())
+        .collect();
+    open_doors.sort();
+    let progress = world.resource::<LevelProgress>();
+
+    Ok(SaveGame {
+        version: SAVE_VERSION,
+        level: world.resource::<CurrentLevel>().0.clone(),
+        player_transform,
+        camera_yaw,
+        camera_pitch,
+        last_checkpoint: world.resource::<LastCheckpoint>().0,
+        bodies,
+        unlit_torches,
+        collected_items,
+        elapsed_seconds: progress.elapsed_seconds,
+        exit_reached: progress.exit_reached,
+        inventory: world.resource::<Inventory>().items.clone(),
+        open_doors,
+    })
+}
+
+fn apply_save_game(world: &mut World, save: &SaveGame) -> String {
+    let mut player_query = world.query::<(Entity, &mut Player, &mut Health, &mut Transform)>();
+    let mut player = None;
+    if let Ok((entity, mut state, mut health, mut transform)) = player_query.get_single_mut(world) {
+        *state = default();
+        health.restore();
+        *transform = save.player_transform;
+        player = Some(entity);
+    }
+    if let Some(player) = player {
+        world.entity_mut(player).remove::<Dying>();
+        let mut camera_query = world.query_filtered::<(&Parent, &mut Transform), With<Camera3d>>();
+        for (parent, mut transform) in camera_query.iter_mut(world) {
+            if parent.get() == player {
+                transform.rotation = Quat::from_axis_angle(Vec3::Y, save.camera_yaw)
+                    * Quat::from_axis_angle(Vec3::X, save.camera_pitch);
+            }
+        }
+    } else {
+        warn!("There is no player to restore.");
+    }
+    world.resource_mut::<LastCheckpoint>().0 = save.last_checkpoint;
+
+    let saved_bodies: HashMap<&str, &SavedBody> = save
+        .bodies
+        .iter()
+        .map(|body| (body.name.as_str(), body))
+        .collect();
+    let mut restored = 0;
+    let mut body_query = world.query::<(&Name, &mut Transform, &mut Velocity)>();
+    for (name, mut transform, mut velocity) in body_query.iter_mut(world) {
+        let Some(body) = saved_bodies.get(name.as_str()) else {
+            continue;
+        };
+        transform.translation = body.translation;
+        transform.rotation = body.rotation;
+        velocity.linvel = body.linvel;
+        velocity.angvel = body.angvel;
+        restored += 1;
+    }
+    if restored < save.bodies.len() {
+        // e.g. bodies that were spawned from the console.
+        warn!(
+            "{} saved bodies no longer exist.",
+            save.bodies.len() - restored
+        );
+    }
+
+    let mut torch_query = world.query::<(&Name, &mut Torch)>();
+    for (name, mut torch) in torch_query.iter_mut(world) {
+        let lit = !save.unlit_torches.contains(&name.to_string());
+        if torch.is_lit() != lit {
+            torch.set_lit(lit);
+        }
+    }
+
+    let mut collectible_query = world.query::<(&Name, &mut Collectible)>();
+    for (name, mut collectible) in collectible_query.iter_mut(world) {
+        let collected = save.collected_items.contains(&name.to_string());
+        if collectible.is_collected() != collected {
+            collectible.set_collected(collected);
+        }
+    }
+    world.resource_mut::<Inventory>().items = save.inventory.clone();
+    let mut door_query = world.query::<(&Name, &mut Door)>();
+    for (name, mut door) in door_query.iter_mut(world) {
+        let open = save.open_doors.contains(&name.to_string());
+        if door.is_open() != open {
+            door.set_open(open);
+        }
+    }
+    let mut progress = world.resource_mut::<LevelProgress>();
+    progress.elapsed_seconds = save.elapsed_seconds;
+    progress.exit_reached = save.exit_reached;
+    // Worked out again from everything else that was restored.
+    progress.completed = false;
+    format!("Restored the player and {} bodies.", restored)
+}
+
+/// Saves the game in progress to the given slot.
+pub fn save_to_slot(world: &mut World, slot: &str) -> Result<String, String> {
+    if *world.resource::<State<AppState>>().get() != AppState::InGame {
+        return Err("Can't save while a level is loading.".to_string());
+    }
+    let save = capture_save_game(world)?;
+    let json = serde_json::to_string_pretty(&save).map_err(|error| error.to_string())?;
+    write_slot(world, slot, &json)?;
+    Ok(format!("Saved to slot '{}'.", slot))
+}
+
+/// Loads a saved game, first loading its level if it isn't the current one.
+pub fn load_from_slot(world: &mut World, slot: &str) -> Result<String, String> {
+    if *world.resource::<State<AppState>>().get() != AppState::InGame {
+        return Err("Can't load while a level is loading.".to_string());
+    }
+    let json = read_slot(world, slot)?;
+    let save: SaveGame = serde_json::from_str(&json)
+        .map_err(|error| format!("Couldn't parse slot '{}': {}", slot, error))?;
+    if save.version != SAVE_VERSION {
+        return Err(format!(
+            "Slot '{}' is from version {} of the game, but this is version {}.",
+            slot, save.version, SAVE_VERSION
+        ));
+    }
+
+    if save.level == world.resource::<CurrentLevel>().0 {
+        let message = apply_save_game(world, &save);
+        return Ok(format!("Loaded slot '{}'. {}", slot, message));
+    }
+    let level = save.level.clone();
+    world.insert_resource(CurrentLevel(level.clone()));
+    world.insert_resource(PendingLoad(save));
+    world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::LoadingAssets);
+    Ok(format!("Loading level {} for slot '{}'...", level, slot))
+}
+
+fn apply_pending_load(world: &mut World) {
+    if let Some(PendingLoad(save)) = world.remove_resource::<PendingLoad>() {
+        info!("{}", apply_save_game(world, &save));
+    }
+}
+
+/// Which of `SELECTABLE_SLOTS` the save and load keys use.
+#[derive(Resource, Default)]
+struct SelectedSlot(usize);
+
+/// What the last save, load or slot change did, shown for a few seconds.
+#[derive(Resource, Default)]
+struct SaveMessage {
+    text: String,
+    seconds_left: f32,
+}
+
+#[derive(Component)]
+struct SaveMessageUi;
+
+#[derive(Component)]
+struct SaveMessageText;
+
+fn show_message(world: &mut World, text: String) {
+    *world.resource_mut::<SaveMessage>() = SaveMessage {
+        text,
+        seconds_left: MESSAGE_DURATION,
+    };
+}
+
+fn show_result(world: &mut World, result: Result<String, String>) {
+    let text = match result {
+        Ok(message) => {
+            info!("{}", message);
+            message
+        }
+        Err(error) => {
+            warn!("{}", error);
+            error
+        }
+    };
+    show_message(world, text);
+}
+
+fn save_keys(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut selected: ResMut<SelectedSlot>,
+) {
+    if keys.just_pressed(SELECT_SLOT_KEY) {
+        selected.0 = (selected.0 + 1) % SELECTABLE_SLOTS.len();
+        let (_, name) = SELECTABLE_SLOTS[selected.0];
+        let text = format!("{} selected. F5 saves to it and F9 loads it.", name);
+        commands.add(|world: &mut World| show_message(world, text));
+    }
+    let (slot, _) = SELECTABLE_SLOTS[selected.0];
+    if keys.just_pressed(QUICKSAVE_KEY) {
+        commands.add(move |world: &mut World| {
+            let result = save_to_slot(world, slot);
+            show_result(world, result);
+        });
+    }
+    if keys.just_pressed(QUICKLOAD_KEY) {
+        commands.add(move |world: &mut World| {
+            let result = load_from_slot(world, slot);
+            show_result(world, result);
+        });
+    }
+}
+
+fn setup_save_message(mut commands: Commands, fonts: Res<Fonts>) {
+    spawn_centered_hud_text(
+        &mut commands,
+        &fonts,
+        Style {
+            top: Val::Px(10.0),
+            ..default()
+        },
+        (SaveMessageUi, Name::new("SaveMessage")),
+        SaveMessageText,
+    );
+}
+
+fn update_save_message(
+    time: Res<Time>,
+    mut message: ResMut<SaveMessage>,
+    mut query: Query<&mut Text, With<SaveMessageText>>,
+) {
+    if message.seconds_left > 0.0 {
+        message.seconds_left -= time.delta_seconds();
+        if message.seconds_left <= 0.0 {
+            message.text.clear();
+        }
+    }
+    for mut text in &mut query {
+        if text.sections[0].value != message.text {
+            text.sections[0].value = message.text.clone();
+        }
+    }
+}
+
+fn despawn_save_message(mut commands: Commands, query: Query<Entity, With<SaveMessageUi>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn slot_argument<'a>(args: &[&'a str]) -> Result<&'a str, String> {
+    let slot = match args {
+        [] => QUICKSAVE_SLOT,
+        [slot] => slot,
+        _ => return Err("Expected at most one slot name.".to_string()),
+    };
+    if slot.is_empty()
+        || !slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid slot '{}', use only letters, digits, '-' and '_'.",
+            slot
+        ));
+    }
+    Ok(slot)
+}
+
+fn save_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    save_to_slot(world, slot_argument(args)?)
+}
+
+fn load_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    load_from_slot(world, slot_argument(args)?)
+}
+
+/// The directory that saves go in.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+struct SaveDirectory(PathBuf);
+
+#[cfg(not(target_arch = "wasm32"))]
+fn slot_path(world: &World, slot: &str) -> PathBuf {
+    world
+        .resource::<SaveDirectory>()
+        .0
+        .join(format!("{}.json", slot))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_slot(world: &World, slot: &str, contents: &str) -> Result<(), String> {
+    std::fs::create_dir_all(&world.resource::<SaveDirectory>().0)
+        .map_err(|error| error.to_string())?;
+    std::fs::write(slot_path(world, slot), contents).map_err(|error| error.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_slot(world: &World, slot: &str) -> Result<String, String> {
+    std::fs::read_to_string(slot_path(world, slot))
+        .map_err(|error| format!("Couldn't read slot '{}': {}", slot, error))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn saved_slots(world: &World) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(&world.resource::<SaveDirectory>().0) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect()
+}
+
+/// On the web, saves go in the browser's local storage under keys with this prefix.
+#[cfg(target_arch = "wasm32")]
+const STORAGE_KEY_PREFIX: &str = "bevy-fun2-save-";
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, String> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| "Browser storage isn't available.".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_slot(_world: &World, slot: &str, contents: &str) -> Result<(), String> {
+    local_storage()?
+        .set_item(&format!("{}{}", STORAGE_KEY_PREFIX, slot), contents)
+        .map_err(|_| "Couldn't write to browser storage, it may be full.".to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_slot(_world: &World, slot: &str) -> Result<String, String> {
+    local_storage()?
+        .get_item(&format!("{}{}", STORAGE_KEY_PREFIX, slot))
+        .ok()
+        .flatten()
+        .ok_or_else(|| format!("There is no save in slot '{}'.", slot))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn saved_slots(_world: &World) -> Vec<String> {
+    let Ok(storage) = local_storage() else {
+        return vec![];
+    };
+    let length = storage.length().unwrap_or(0);
+    (0..length)
+        .filter_map(|index| storage.key(index).ok().flatten())
+        .filter_map(|key| Some(key.strip_prefix(STORAGE_KEY_PREFIX)?.to_string()))
+        .collect()
+}
+
+/// Saves and loads the player, the camera, every dynamic body and the state of
+/// the level, e.g. what's been picked up and which doors are open, to named
+/// slots. `F5` and `F9` save and load the selected slot, which is the
+/// quicksave slot until `F6` picks another.
+pub struct SaveGamePlugin {
+    /// The directory that saves go in. On the web, they go in the browser's
+    /// local storage instead.
+    pub directory: PathBuf,
+}
+
+impl Default for SaveGamePlugin {
+    fn default() -> Self {
+        SaveGamePlugin {
+            directory: PathBuf::from("saves"),
+        }
+    }
+}
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut App) {
+        #[cfg(not(target_arch = "wasm32"))]
+        app.insert_resource(SaveDirectory(self.directory.clone()));
+
+        app.init_resource::<SelectedSlot>()
+            .init_resource::<SaveMessage>()
+            .init_resource::<Fonts>()
+            .add_systems(
+                OnEnter(AppState::InGame),
+                (apply_pending_load, setup_save_message),
+            )
+            .add_systems(OnExit(AppState::InGame), despawn_save_message)
+            .add_systems(
+                Update,
+                (save_keys, update_save_message).run_if(in_state(AppState::InGame)),
+            )
+            .add_console_command(ConsoleCommand::new(
+                "save",
+                "save [slot] - save the game, to the quicksave slot by default",
+                save_command,
+            ))
+            .add_console_command(
+                ConsoleCommand::new(
+                    "load",
+                    "load [slot] - load a saved game, from the quicksave slot by default",
+                    load_command,
+                )
+                .with_arguments(saved_slots),
+            );
+    }
+}
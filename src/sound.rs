@@ -0,0 +1,438 @@
+use std::time::Duration;
+
+use bevy::{
+    audio::{AudioPlugin, Volume},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    player::{Player, PlayerForcePush, PlayerMovement},
+    time_scale::FIXED_TIMESTEP_SECONDS,
+    torch::Torch,
+};
+
+/// The distance between the listener's ears, in meters.
+const EAR_GAP: f32 = 0.2;
+
+/// One-shot sounds are despawned after this long even if they never played,
+/// e.g. because there's no audio device, so that they don't pile up.
+const ONE_SHOT_SOUND_LIFETIME: Duration = Duration::from_secs(5);
+
+/// Bodies don't make another impact sound until this long after the last one,
+/// otherwise a body sliding along the floor would make one every step.
+const IMPACT_SOUND_COOLDOWN: Duration = Duration::from_millis(150);
+
+/// What a sound's volume is configured by.
+#[derive(Clone, Copy)]
+enum SoundCategory {
+    Footsteps,
+    Impacts,
+    Effects,
+    Ambience,
+}
+
+impl SoundCategory {
+    fn volume(&self, config: &Config) -> f32 {
+        let volume = match self {
+            SoundCategory::Footsteps => config.audio_footsteps_volume,
+            SoundCategory::Impacts => config.audio_impacts_volume,
+            SoundCategory::Effects => config.audio_effects_volume,
+            SoundCategory::Ambience => config.audio_ambience_volume,
+        };
+        config.audio_master_volume * volume
+    }
+}
+
+/// A sound that's playing, or about to.
+#[derive(Component)]
+struct Sound {
+    category: SoundCategory,
+    /// How loud this particular sound is, relative to the rest of its category.
+    gain: f32,
+}
+
+#[derive(Component)]
+struct OneShotSound(Timer);
+
+/// The crackling of a torch, which stops while the torch is out.
+#[derive(Component)]
+struct TorchSound;
+
+/// When a rigid body last made an impact sound.
+#[derive(Component)]
+struct ImpactSoundCooldown(Timer);
+
+/// What the player is walking on, which changes the sound of their footsteps.
+#[derive(Clone, Copy)]
+enum Surface {
+    Stone,
+    Wood,
+}
+
+impl Surface {
+    /// Works out what something is made of from its name in Blender.
+    fn from_name(name: &str) -> Self {
+        if name.contains("Crate") || name.contains("Barrel") {
+            Surface::Wood
+        } else {
+            Surface::Stone
+        }
+    }
+}
+
+#[derive(Resource)]
+struct Sounds {
+    stone_footsteps: Vec<Handle<AudioSource>>,
+    wood_footsteps: Vec<Handle<AudioSource>>,
+    wood_impact: Handle<AudioSource>,
+    force_push: Handle<AudioSource>,
+    torch_crackle: Handle<AudioSource>,
+}
+
+impl Sounds {
+    fn footsteps(&self, surface: Surface) -> &[Handle<AudioSource>] {
+        match surface {
+            Surface::Stone => &self.stone_footsteps,
+            Surface::Wood => &self.wood_footsteps,
+        }
+    }
+}
+
+/// How far the player has walked since their last footstep.
+#[derive(Resource, Default)]
+struct FootstepState {
+    distance: f32,
+    last_position: Option<Vec3>,
+    /// Which variation of the footstep sound to play next.
+    next_variation: usize,
+}
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let footsteps = |surface: &str| {
+        (1..=3)
+            .map(|index| asset_server.load(format!("sounds/footstep_{}_{}.wav", surface, index)))
+            .collect()
+    };
+    commands.insert_resource(Sounds {
+        stone_footsteps: footsteps("stone"),
+        wood_footsteps: footsteps("wood"),
+        wood_impact: asset_server.load("sounds/impact_wood.wav"),
+        force_push: asset_server.load("sounds/force_push.wav"),
+        torch_crackle: asset_server.load("sounds/torch_crackle.wav"),
+    });
+}
+
+fn play_one_shot(
+    commands: &mut Commands,
+    source: Handle<AudioSource>,
+    category: SoundCategory,
+    gain: f32,
+    config: &Config,
+) {
+    let volume = Volume::new_absolute(category.volume(config) * gain);
+    commands.spawn((
+        AudioBundle {
+            source,
+            settings: PlaybackSettings::DESPAWN.with_volume(volume),
+        },
+        Sound { category, gain },
+        OneShotSound(Timer::new(ONE_SHOT_SOUND_LIFETIME, TimerMode::Once)),
+    ));
+}
+
+/// Plays a sound from a point in the world, as heard by the camera.
+fn play_one_shot_at(
+    commands: &mut Commands,
+    source: Handle<AudioSource>,
+    category: SoundCategory,
+    gain: f32,
+    position: Vec3,
+    listener: Transform,
+    config: &Config,
+) {
+    let volume = Volume::new_absolute(category.volume(config) * gain);
+    commands.spawn((
+        SpatialAudioBundle {
+            source,
+            settings: PlaybackSettings::DESPAWN.with_volume(volume),
+            spatial: SpatialSettings::new(listener, EAR_GAP, position),
+        },
+        TransformBundle::from_transform(Transform::from_translation(position)),
+        Sound { category, gain },
+        OneShotSound(Timer::new(ONE_SHOT_SOUND_LIFETIME, TimerMode::Once)),
+    ));
+}
+
+fn listener_transform(camera_query: &Query<&GlobalTransform, With<Camera3d>>) -> Transform {
+    camera_query
+        .get_single()
+        .map_or(Transform::IDENTITY, |camera| camera.compute_transform())
+}
+
+fn play_footsteps(
+    mut commands: Commands,
+    mut state: ResMut<FootstepState>,
+    mut player_movement: EventReader<PlayerMovement>,
+    player_query: Query<(Entity, &Player, &Transform)>,
+    rapier_context: Res<RapierContext>,
+    names: Query<&Name>,
+    sounds: Res<Sounds>,
+    config: Res<Config>,
+) {
+    let moving = player_movement.iter().count() > 0;
+    let Ok((entity, player, transform)) = player_query.get_single() else {
+        return;
+    };
+    let position = transform.translation;
+    let last_position = state.last_position.replace(position).unwrap_or(position);
+    if !moving || !player.is_grounded() {
+        return;
+    }
+    // We only count horizontal distance, so that walking into a wall or
+    // riding something up and down doesn't make footsteps.
+    let moved = position - last_position;
+    state.distance += Vec2::new(moved.x, moved.z).length();
+    if state.distance < config.footstep_stride {
+        return;
+    }
+    state.distance = 0.0;
+
+    let max_toi = config.player_capsule_radius + config.player_snap_to_ground_distance + 0.1;
+    let filter = QueryFilter::new()
+        .exclude_rigid_body(entity)
+        .exclude_sensors();
+    let surface = rapier_context
+        .cast_ray(position, -Vec3::Y, max_toi, true, filter)
+        .and_then(|(ground, _)| names.get(ground).ok())
+        .map_or(Surface::Stone, |name| Surface::from_name(name));
+    let footsteps = sounds.footsteps(surface);
+    let source = footsteps[state.next_variation % footsteps.len()].clone();
+    state.next_variation += 1;
+    let gain = if player.is_sprinting() { 1.0 } else { 0.7 };
+    play_one_shot(
+        &mut commands,
+        source,
+        SoundCategory::Footsteps,
+        gain,
+        &config,
+    );
+}
+
+fn play_force_push_sound(
+    mut commands: Commands,
+    mut force_pushes: EventReader<PlayerForcePush>,
+    sounds: Res<Sounds>,
+    config: Res<Config>,
+) {
+    for _ in force_pushes.iter() {
+        play_one_shot(
+            &mut commands,
+            sounds.force_push.clone(),
+            SoundCategory::Effects,
+            1.0,
+            &config,
+        );
+    }
+}
+
+/// Lets rigid bodies report how hard they're hit, so they can make sounds.
+fn enable_impact_events(
+    mut commands: Commands,
+    query: Query<(Entity, &Name), (Added<RigidBody>, With<Collider>)>,
+    config: Res<Config>,
+) {
+    for (entity, name) in &query {
+        if !name.contains("-rigid") {
+            continue;
+        }
+        // Rapier measures contacts by force rather than impulse, and we get one
+        // event per step.
+        let min_force = config.impact_sound_min_impulse / FIXED_TIMESTEP_SECONDS as f32;
+        let mut cooldown = Timer::new(IMPACT_SOUND_COOLDOWN, TimerMode::Once);
+        cooldown.tick(IMPACT_SOUND_COOLDOWN);
+        commands.entity(entity).insert((
+            ActiveEvents::CONTACT_FORCE_EVENTS,
+            ContactForceEventThreshold(min_force),
+            ImpactSoundCooldown(cooldown),
+        ));
+    }
+}
+
+/// Contact force events only last for a couple of physics steps, so we handle
+/// them in `FixedUpdate` rather than risk missing them.
+fn play_impact_sounds(
+    mut commands: Commands,
+    mut contact_forces: EventReader<ContactForceEvent>,
+    mut bodies: Query<(&GlobalTransform, &mut ImpactSoundCooldown)>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    sounds: Res<Sounds>,
+    config: Res<Config>,
+) {
+    let step = Duration::from_secs_f64(FIXED_TIMESTEP_SECONDS);
+    for (_, mut cooldown) in &mut bodies {
+        cooldown.0.tick(step);
+    }
+    let listener = listener_transform(&camera_query);
+    for event in contact_forces.iter() {
+        let impulse = event.total_force_magnitude * FIXED_TIMESTEP_SECONDS as f32;
+        if impulse < config.impact_sound_min_impulse {
+            continue;
+        }
+        // Only bodies that make impact sounds have a cooldown.
+        let body = if bodies.contains(event.collider1) {
+            event.collider1
+        } else {
+            event.collider2
+        };
+        let Ok((transform, mut cooldown)) = bodies.get_mut(body) else {
+            continue;
+        };
+        if !cooldown.0.finished() {
+            continue;
+        }
+        cooldown.0.reset();
+        let gain = (impulse / config.impact_sound_max_impulse).min(1.0);
+        play_one_shot_at(
+            &mut commands,
+            sounds.wood_impact.clone(),
+            SoundCategory::Impacts,
+            gain,
+            transform.translation(),
+            listener,
+            &config,
+        );
+    }
+}
+
+fn add_torch_sounds(
+    mut commands: Commands,
+    query: Query<(Entity, &GlobalTransform), With<Torch>>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    sounds: Res<Sounds>,
+    config: Res<Config>,
+) {
+    let listener = listener_transform(&camera_query);
+    let mut count = 0;
+    for (entity, transform) in &query {
+        let category = SoundCategory::Ambience;
+        // The torches all share the same loop, so vary their speed a little to
+        // keep them from crackling in unison.
+        let speed = 0.9 + 0.05 * (count % 5) as f32;
+        count += 1;
+        let sound = commands
+            .spawn((
+                SpatialAudioBundle {
+                    source: sounds.torch_crackle.clone(),
+                    settings: PlaybackSettings::LOOP
+                        .with_volume(Volume::new_absolute(category.volume(&config)))
+                        .with_speed(speed),
+                    spatial: SpatialSettings::new(listener, EAR_GAP, transform.translation()),
+                },
+                TransformBundle::default(),
+                Sound {
+                    category,
+                    gain: 1.0,
+                },
+                TorchSound,
+                Name::new("TorchSound"),
+            ))
+            .id();
+        commands.entity(entity).add_child(sound);
+    }
+
+    info!("Added sounds to {} torches.", count);
+}
+
+/// Keeps spatial sounds coming from the right place as the camera, and
+/// whatever's making them, moves.
+fn update_spatial_sounds(
+    sinks: Query<(&SpatialAudioSink, &GlobalTransform)>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+) {
+    let listener = listener_transform(&camera_query);
+    for (sink, transform) in &sinks {
+        sink.set_listener_position(listener, EAR_GAP);
+        sink.set_emitter_position(transform.translation());
+    }
+}
+
+fn pause_unlit_torch_sounds(
+    sinks: Query<(&Parent, &SpatialAudioSink), With<TorchSound>>,
+    torches: Query<&Torch>,
+) {
+    for (parent, sink) in &sinks {
+        let Ok(torch) = torches.get(parent.get()) else {
+            continue;
+        };
+        if torch.is_lit() == sink.is_paused() {
+            sink.toggle();
+        }
+    }
+}
+
+fn update_sound_volumes(
+    config: Res<Config>,
+    spatial_sinks: Query<(&SpatialAudioSink, &Sound)>,
+    sinks: Query<(&AudioSink, &Sound)>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+    for (sink, sound) in &spatial_sinks {
+        sink.set_volume(sound.category.volume(&config) * sound.gain);
+    }
+    for (sink, sound) in &sinks {
+        sink.set_volume(sound.category.volume(&config) * sound.gain);
+    }
+}
+
+fn despawn_one_shot_sounds(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut OneShotSound)>,
+) {
+    for (entity, mut one_shot) in &mut query {
+        if one_shot.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Footsteps, impacts, the force push and torches. Does nothing if Bevy's
+/// `AudioPlugin` is disabled, e.g. when running headlessly.
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<AudioPlugin>() {
+            info!("Audio is disabled.");
+            return;
+        }
+        app.init_resource::<FootstepState>()
+            .add_systems(Startup, load_sounds)
+            .add_systems(OnEnter(AppState::InGame), add_torch_sounds)
+            .add_systems(
+                FixedUpdate,
+                (
+                    enable_impact_events.before(PhysicsSet::SyncBackend),
+                    play_impact_sounds.after(PhysicsSet::StepSimulation),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    play_footsteps,
+                    play_force_push_sound,
+                    update_spatial_sounds,
+                    pause_unlit_torch_sounds,
+                    update_sound_volumes,
+                    despawn_one_shot_sounds,
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use bevy::{app::RunFixedUpdateLoop, prelude::*, time::fixed_timestep::run_fixed_update_schedule};
+
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+
+/// How far each step of the simulation (physics and the player) advances it, in
+/// seconds. Simulation systems run in `FixedUpdate`, in lockstep with Rapier.
+pub const FIXED_TIMESTEP_SECONDS: f64 = 1.0 / 60.0;
+
+/// Controls how fast the simulation runs, for debugging. Rather than changing
+/// the timestep, which would change the results, we change how often steps are
+/// taken.
+#[derive(Resource)]
+pub struct SimulationTime {
+    pub scale: f32,
+    pub frozen: bool,
+    step_requested: bool,
+}
+
+impl Default for SimulationTime {
+    fn default() -> Self {
+        SimulationTime {
+            scale: 1.0,
+            frozen: false,
+            step_requested: false,
+        }
+    }
+}
+
+impl SimulationTime {
+    /// Advances the simulation by a single step on the next frame, if it's frozen.
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+
+    /// How far we are from the last step to the next one, from 0 to 1, for
+    /// interpolating what's rendered between steps.
+    pub fn step_fraction(&self, fixed_time: &FixedTime) -> f32 {
+        if self.frozen || self.scale == 0.0 {
+            // Show where things actually are, since there's no next step.
+            return 1.0;
+        }
+        (fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0)
+    }
+
+    fn is_real_time(&self) -> bool {
+        self.scale == 1.0 && !self.frozen
+    }
+}
+
+#[derive(Component)]
+struct TimeScaleText;
+
+fn setup_time_scale_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 24.0,
+                color: Color::YELLOW,
+            },
+        )
+        .with_style(Style {
+            display: Display::None,
+            position_type: PositionType::Absolute,
+            right: Val::Px(10.0),
+            top: Val::Px(10.0),
+            ..default()
+        }),
+        TimeScaleText,
+        Name::new("TimeScaleText"),
+    ));
+}
+
+/// The fixed timestep's period while the simulation is frozen. No amount of
+/// frame time can add up to it, so `FixedUpdate` never runs on its own.
+const FROZEN_PERIOD: Duration = Duration::MAX;
+
+fn apply_simulation_time(sim_time: Res<SimulationTime>, mut fixed_time: ResMut<FixedTime>) {
+    if sim_time.frozen || sim_time.scale == 0.0 {
+        // Throw away the time that passes while we're frozen, so we don't catch
+        // up on it all at once when we resume.
+        *fixed_time = FixedTime::new(FROZEN_PERIOD);
+    } else {
+        // Avoid needlessly triggering change detection, since we run every frame.
+        let period = Duration::from_secs_f64(FIXED_TIMESTEP_SECONDS).div_f32(sim_time.scale);
+        if fixed_time.period != period {
+            fixed_time.period = period;
+        }
+    }
+}
+
+/// Runs `FixedUpdate` exactly once if a step was requested while the simulation
+/// is frozen. This runs after Bevy's own fixed update loop, which doesn't run
+/// while we're frozen.
+fn run_requested_step(world: &mut World) {
+    let mut sim_time = world.resource_mut::<SimulationTime>();
+    if !sim_time.step_requested {
+        return;
+    }
+    sim_time.step_requested = false;
+    if sim_time.frozen || sim_time.scale == 0.0 {
+        world.run_schedule(FixedUpdate);
+    }
+}
+
+fn update_time_scale_text(
+    sim_time: Res<SimulationTime>,
+    mut query: Query<(&mut Text, &mut Style), With<TimeScaleText>>,
+) {
+    if !sim_time.is_changed() {
+        return;
+    }
+    for (mut text, mut style) in &mut query {
+        style.display = if sim_time.is_real_time() {
+            Display::None
+        } else {
+            Display::Flex
+        };
+        text.sections[0].value = if sim_time.frozen {
+            "Frozen".to_string()
+        } else {
+            format!("Time scale: {}x", sim_time.scale)
+        };
+    }
+}
+
+/// `-` and `=` halve and double the time scale, `P` freezes and unfreezes the
+/// simulation, and `.` advances it by a single step while it's frozen.
+#[cfg(feature = "debug_mode")]
+fn time_scale_keys(keys: Res<Input<KeyCode>>, mut sim_time: ResMut<SimulationTime>) {
+    if keys.just_pressed(KeyCode::Minus) {
+        sim_time.scale /= 2.0;
+    }
+    if keys.just_pressed(KeyCode::Equals) {
+        sim_time.scale *= 2.0;
+    }
+    if keys.just_pressed(KeyCode::P) {
+        sim_time.frozen = !sim_time.frozen;
+    }
+    if keys.just_pressed(KeyCode::Period) {
+        sim_time.request_step();
+    }
+}
+
+fn timescale_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let [scale] = args else {
+        return Err("Expected a time scale.".to_string());
+    };
+    let scale: f32 = scale
+        .parse()
+        .ok()
+        .filter(|scale: &f32| *scale >= 0.0)
+        .ok_or_else(|| format!("Invalid time scale '{}'.", scale))?;
+    world.resource_mut::<SimulationTime>().scale = scale;
+    Ok(format!("Time scale set to {}.", scale))
+}
+
+fn physics_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut sim_time = world.resource_mut::<SimulationTime>();
+    match args {
+        ["freeze"] => {
+            sim_time.frozen = true;
+            Ok("Simulation frozen.".to_string())
+        }
+        ["resume"] => {
+            sim_time.frozen = false;
+            Ok("Simulation resumed.".to_string())
+        }
+        ["step"] => {
+            sim_time.frozen = true;
+            sim_time.request_step();
+            Ok("Stepping simulation.".to_string())
+        }
+        _ => Err("Expected 'freeze', 'resume' or 'step'.".to_string()),
+    }
+}
+
+pub struct TimeScalePlugin;
+
+impl Plugin for TimeScalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimulationTime>()
+            .insert_resource(FixedTime::new(Duration::from_secs_f64(
+                FIXED_TIMESTEP_SECONDS,
+            )))
+            .add_systems(Startup, setup_time_scale_text)
+            .add_systems(PreUpdate, apply_simulation_time)
+            .add_systems(
+                RunFixedUpdateLoop,
+                run_requested_step.after(run_fixed_update_schedule),
+            )
+            .add_systems(Update, update_time_scale_text)
+            .add_console_command(ConsoleCommand::new(
+                "timescale",
+                "timescale <scale> - change how fast the simulation runs",
+                timescale_command,
+            ))
+            .add_console_command(
+                ConsoleCommand::new(
+                    "physics",
+                    "physics freeze|resume|step - freeze or single-step the simulation",
+                    physics_command,
+                )
+                .with_arguments(|_| {
+                    vec![
+                        "freeze".to_string(),
+                        "resume".to_string(),
+                        "step".to_string(),
+                    ]
+                }),
+            );
+
+        #[cfg(feature = "debug_mode")]
+        app.add_systems(Update, time_scale_keys);
+    }
+}
@@ -0,0 +1,746 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{
+        primitives::Aabb,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    app_state::AppState,
+    config::Config,
+    interaction::{Interactable, Interacted},
+    light_manager::{update_managed_lights, ManagedLight},
+    player::{Player, PlayerForcePush},
+};
+
+/// How far a point light can be from a torch's flame to count as its light.
+const TORCH_LIGHT_MAX_DISTANCE: f32 = 2.0;
+
+/// How close something has to come to the top of a torch to put it out, in
+/// meters.
+const TORCH_FLAME_RADIUS: f32 = 0.15;
+
+/// How far from the centre of the view, in radians, the player can be looking
+/// and still be aiming at a torch.
+const TORCH_AIM_ANGLE: f32 = 0.2;
+
+/// The radius of the sensor around a torch's flame that the player looks at
+/// to light it.
+const TORCH_INTERACTION_RADIUS: f32 = 0.25;
+
+/// The width and height of the soft dot that particles are drawn with.
+const PARTICLE_TEXTURE_SIZE: u32 = 32;
+
+/// A torch on a wall, made of the mesh primitives named `TorchCylinder` in
+/// Blender.
+#[derive(Component)]
+pub struct Torch {
+    /// Where the top of the flame is, in world space.
+    pub flame_position: Vec3,
+    /// The point light that the torch lights its surroundings with, if any.
+    pub light: Option<Entity>,
+    flame: Option<TorchFlame>,
+    /// The sensor around the flame that the player uses to light the torch.
+    interaction: Entity,
+    lit: bool,
+    /// Whether the torch was lit the last time we updated its light and
+    /// flame.
+    was_lit: bool,
+}
+
+impl Torch {
+    pub fn is_lit(&self) -> bool {
+        self.lit
+    }
+
+    pub fn set_lit(&mut self, lit: bool) {
+        self.lit = lit;
+    }
+}
+
+/// The glowing part of a torch's mesh.
+struct TorchFlame {
+    entity: Entity,
+    lit_material: Handle<StandardMaterial>,
+    unlit_material: Handle<StandardMaterial>,
+}
+
+/// The `Interactable` for lighting a torch, which is separate from the torch
+/// itself so that it can be placed around the flame.
+#[derive(Component)]
+struct TorchInteraction {
+    torch: Entity,
+}
+
+/// Sent when a torch is lit or goes out.
+#[derive(Event)]
+pub struct TorchToggled {
+    pub torch: Entity,
+    pub lit: bool,
+}
+
+/// A torch's point light, which flickers around how it was set up in Blender.
+#[derive(Component)]
+struct TorchLight {
+    intensity: f32,
+    translation: Vec3,
+    /// Offsets this light's noise so that torches don't flicker in unison.
+    seed: f32,
+}
+
+#[derive(Clone, Copy)]
+enum ParticleKind {
+    Flame,
+    Ember,
+    Smoke,
+}
+
+/// A flame, ember or puff of smoke rising from a torch. Particles are never
+/// despawned when they die, but reset to the start of a new life instead.
+#[derive(Component)]
+struct Particle {
+    kind: ParticleKind,
+    torch: Entity,
+    origin: Vec3,
+    age: f32,
+    lifetime: f32,
+    velocity: Vec3,
+}
+
+#[derive(Resource)]
+struct ParticleAssets {
+    mesh: Handle<Mesh>,
+    flame: Handle<StandardMaterial>,
+    ember: Handle<StandardMaterial>,
+    smoke: Handle<StandardMaterial>,
+}
+
+/// A small, fast random number generator, since particles don't need good
+/// randomness.
+#[derive(Resource)]
+struct ParticleRng(u32);
+
+impl ParticleRng {
+    /// Returns a number between 0 and 1.
+    fn next(&mut self) -> f32 {
+        // Xorshift32.
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next()
+    }
+}
+
+/// Smoothly varying noise between -1 and 1, which changes about once per unit
+/// of `t`.
+fn smooth_noise(t: f32) -> f32 {
+    let hash = |n: i32| {
+        let n = (n as u32).wrapping_mul(0x27d4eb2d) ^ 0x165667b1;
+        let n = (n ^ (n >> 15)).wrapping_mul(0x85ebca6b);
+        (n ^ (n >> 13)) as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    let i = t.floor();
+    let f = t - i;
+    let smooth = f * f * (3.0 - 2.0 * f);
+    let a = hash(i as i32);
+    let b = hash(i as i32 + 1);
+    a + (b - a) * smooth
+}
+
+/// Noise with some finer detail on top, which looks more like a flame.
+fn flicker_noise(t: f32) -> f32 {
+    (smooth_noise(t) * 2.0 + smooth_noise(t * 2.7 + 17.0)) / 3.0
+}
+
+fn particle_texture() -> Image {
+    let size = PARTICLE_TEXTURE_SIZE;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let center = (size as f32 - 1.0) / 2.0;
+            let distance = Vec2::new(x as f32 - center, y as f32 - center).length() / center;
+            let alpha = (1.0 - distance).clamp(0.0, 1.0).powf(1.5);
+            data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn setup_particle_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let texture = images.add(particle_texture());
+    let mut material = |color: Color, alpha_mode: AlphaMode| {
+        materials.add(StandardMaterial {
+            base_color: color,
+            base_color_texture: Some(texture.clone()),
+            alpha_mode,
+            unlit: true,
+            ..default()
+        })
+    };
+    commands.insert_resource(ParticleAssets {
+        mesh: meshes.add(shape::Quad::new(Vec2::ONE).into()),
+        // These are brighter than white so that they bloom.
+        flame: material(Color::rgb(4.0, 1.6, 0.4), AlphaMode::Add),
+        ember: material(Color::rgb(6.0, 2.0, 0.3), AlphaMode::Add),
+        smoke: material(Color::rgba(0.08, 0.07, 0.07, 0.4), AlphaMode::Blend),
+    });
+}
+
+/// Whether `entity` is `ancestor` or one of its descendants.
+fn is_descendant_of(entity: Entity, ancestor: Entity, parents: &Query<&Parent>) -> bool {
+    let mut current = entity;
+    loop {
+        if current == ancestor {
+            return true;
+        }
+        let Ok(parent) = parents.get(current) else {
+            return false;
+        };
+        current = parent.get();
+    }
+}
+
+/// The primitives of a torch that we've found so far.
+struct TorchParts {
+    node: Entity,
+    min: Vec3,
+    max: Vec3,
+    flame: Option<Entity>,
+}
+
+/// Finds the torches in the scene, and the lights that go with them.
+fn setup_torches(
+    mut commands: Commands,
+    primitives: Query<
+        (Entity, &Name, &Parent, &Aabb, &Handle<StandardMaterial>),
+        With<Handle<Mesh>>,
+    >,
+    parents: Query<&Parent>,
+    global_transforms: Query<&GlobalTransform>,
+    lights: Query<(Entity, &GlobalTransform, &Transform, &PointLight)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // Each torch has a primitive for its handle and another for its flame, so
+    // we group them by the node they belong to.
+    let mut found: Vec<TorchParts> = vec![];
+    for (entity, name, parent, aabb, material) in &primitives {
+        if !name.starts_with("TorchCylinder") {
+            continue;
+        }
+        let (min, max) = (Vec3::from(aabb.min()), Vec3::from(aabb.max()));
+        let is_flame = materials
+            .get(material)
+            .is_some_and(|material| material.emissive != Color::BLACK);
+        let parts = match found.iter_mut().find(|parts| parts.node == parent.get()) {
+            Some(parts) => {
+                parts.min = parts.min.min(min);
+                parts.max = parts.max.max(max);
+                parts
+            }
+            None => {
+                found.push(TorchParts {
+                    node: parent.get(),
+                    min,
+                    max,
+                    flame: None,
+                });
+                found.last_mut().unwrap()
+            }
+        };
+        if is_flame {
+            parts.flame = Some(entity);
+        }
+    }
+
+    let torch_count = found.len();
+    let mut used_lights = vec![];
+    for (index, parts) in found.into_iter().enumerate() {
+        let Ok(global_transform) = global_transforms.get(parts.node) else {
+            continue;
+        };
+        let (min, max) = (parts.min, parts.max);
+        let top = Vec3::new((min.x + max.x) / 2.0, max.y, (min.z + max.z) / 2.0);
+        let flame_position = global_transform.transform_point(top);
+        // A light parented to the torch in Blender is obviously its light,
+        // otherwise we go with the nearest one.
+        let light = lights
+            .iter()
+            .filter(|(entity, ..)| !used_lights.contains(entity))
+            .find(|(entity, ..)| is_descendant_of(*entity, parts.node, &parents))
+            .or_else(|| {
+                lights
+                    .iter()
+                    .filter(|(entity, ..)| !used_lights.contains(entity))
+                    .filter(|(_, transform, ..)| {
+                        transform.translation().distance(flame_position) < TORCH_LIGHT_MAX_DISTANCE
+                    })
+                    .min_by(|(_, a, ..), (_, b, ..)| {
+                        let a = a.translation().distance(flame_position);
+                        let b = b.translation().distance(flame_position);
+                        a.total_cmp(&b)
+                    })
+            })
+            .map(|(entity, ..)| entity);
+        if let Some(light) = light {
+            used_lights.push(light);
+            let (_, _, transform, point_light) = lights.get(light).unwrap();
+            commands.entity(light).insert(TorchLight {
+                intensity: point_light.intensity,
+                translation: transform.translation,
+                seed: index as f32 * 31.7,
+            });
+        }
+        // Torches share the flame's material, so a torch that goes out swaps
+        // it for a dark copy rather than changing it.
+        let flame = parts.flame.and_then(|flame| {
+            let (.., material) = primitives.get(flame).ok()?;
+            let lit_material = materials.get(material)?;
+            let unlit_material = StandardMaterial {
+                emissive: Color::BLACK,
+                base_color: lit_material.base_color * 0.2,
+                ..lit_material.clone()
+            };
+            Some(TorchFlame {
+                entity: flame,
+                lit_material: material.clone(),
+                unlit_material: materials.add(unlit_material),
+            })
+        });
+        let interaction = commands
+            .spawn((
+                TransformBundle::from_transform(Transform::from_translation(flame_position)),
+                Collider::ball(TORCH_INTERACTION_RADIUS),
+                Sensor,
+                Interactable {
+                    prompt: "Light torch".to_string(),
+                    // Torches start out lit.
+                    enabled: false,
+                },
+                TorchInteraction { torch: parts.node },
+                Name::new("TorchInteraction"),
+            ))
+            .id();
+        commands.entity(parts.node).insert(Torch {
+            flame_position,
+            light,
+            flame,
+            interaction,
+            lit: true,
+            was_lit: true,
+        });
+    }
+
+    info!(
+        "Found {} torches, {} with lights.",
+        torch_count,
+        used_lights.len()
+    );
+}
+
+fn flicker_torch_lights(
+    time: Res<Time>,
+    mut lights: Query<(&TorchLight, &mut ManagedLight, &mut Transform)>,
+    config: Res<Config>,
+) {
+    let enabled = config.torch_flicker_enabled && !config.low_quality;
+    let t = time.elapsed_seconds() * config.torch_flicker_speed;
+    for (torch_light, mut managed, mut transform) in &mut lights {
+        let (intensity, translation) = if enabled {
+            let seed = torch_light.seed;
+            let wander = Vec3::new(
+                flicker_noise(t + seed + 100.0),
+                flicker_noise(t + seed + 200.0),
+                flicker_noise(t + seed + 300.0),
+            );
+            (
+                torch_light.intensity
+                    * (1.0 + config.torch_flicker_amount * flicker_noise(t + seed)),
+                torch_light.translation + wander * config.torch_flicker_distance,
+            )
+        } else {
+            (torch_light.intensity, torch_light.translation)
+        };
+        // Avoid needlessly triggering change detection when we're not flickering.
+        if managed.intensity != intensity {
+            managed.intensity = intensity;
+        }
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+/// Updates torches' lights and flames to match whether they're lit.
+fn update_lit_torches(
+    mut torches: Query<(Entity, &mut Torch), Changed<Torch>>,
+    mut lights: Query<&mut ManagedLight>,
+    mut interactables: Query<&mut Interactable>,
+    mut commands: Commands,
+    mut toggled: EventWriter<TorchToggled>,
+) {
+    for (entity, mut torch) in &mut torches {
+        let lit = torch.lit;
+        if lit != torch.was_lit {
+            torch.bypass_change_detection().was_lit = lit;
+            toggled.send(TorchToggled { torch: entity, lit });
+        }
+        if let Some(light) = torch.light {
+            if let Ok(mut managed) = lights.get_mut(light) {
+                managed.enabled = lit;
+            }
+        }
+        if let Ok(mut interactable) = interactables.get_mut(torch.interaction) {
+            interactable.enabled = !lit;
+        }
+        if let Some(flame) = &torch.flame {
+            let material = if lit {
+                &flame.lit_material
+            } else {
+                &flame.unlit_material
+            };
+            commands.entity(flame.entity).insert(material.clone());
+        }
+    }
+}
+
+/// Whether there's nothing solid between `from` and a torch's flame.
+fn can_see_flame(
+    rapier_context: &RapierContext,
+    from: Vec3,
+    flame_position: Vec3,
+    filter: QueryFilter,
+) -> bool {
+    let offset = flame_position - from;
+    let distance = offset.length();
+    rapier_context
+        .cast_ray(from, offset / distance, distance, true, filter)
+        .map_or(true, |(_, toi)| toi >= distance - TORCH_FLAME_RADIUS)
+}
+
+/// The torch that the player is looking at out of those that `filter`
+/// accepts, if it's within `max_distance`.
+fn torch_in_view<'a>(
+    torches: impl Iterator<Item = (Entity, &'a Torch)>,
+    camera: &GlobalTransform,
+    max_distance: f32,
+    rapier_context: &RapierContext,
+    filter: QueryFilter,
+) -> Option<Entity> {
+    let eye = camera.translation();
+    let forward = camera.forward();
+    torches
+        .filter_map(|(entity, torch)| {
+            let offset = torch.flame_position - eye;
+            let angle = offset.angle_between(forward);
+            (offset.length() <= max_distance && angle <= TORCH_AIM_ANGLE)
+                .then_some((entity, torch, angle))
+        })
+        .filter(|(_, torch, _)| can_see_flame(rapier_context, eye, torch.flame_position, filter))
+        .min_by(|(.., a), (.., b)| a.total_cmp(b))
+        .map(|(entity, ..)| entity)
+}
+
+/// Blows out the torch that the player force pushes at.
+fn extinguish_torches_with_force_push(
+    mut force_pushes: EventReader<PlayerForcePush>,
+    mut torches: Query<(Entity, &mut Torch)>,
+    camera_query: Query<(&Parent, &GlobalTransform), With<Camera3d>>,
+    player_query: Query<Entity, With<Player>>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+) {
+    if force_pushes.iter().count() == 0 {
+        return;
+    }
+    for (parent, camera) in &camera_query {
+        let Ok(player) = player_query.get(parent.get()) else {
+            continue;
+        };
+        let filter = QueryFilter::new()
+            .exclude_sensors()
+            .exclude_rigid_body(player);
+        let lit_torches = torches.iter().filter(|(_, torch)| torch.lit);
+        let Some(target) = torch_in_view(
+            lit_torches,
+            camera,
+            config.player_force_push_max_distance,
+            &rapier_context,
+            filter,
+        ) else {
+            continue;
+        };
+        if let Ok((_, mut torch)) = torches.get_mut(target) {
+            info!("Blew out a torch.");
+            torch.set_lit(false);
+        }
+    }
+}
+
+/// Puts out torches that something like a barrel is thrown into.
+fn extinguish_torches_hit_by_bodies(
+    mut torches: Query<&mut Torch>,
+    velocities: Query<&Velocity>,
+    rapier_context: Res<RapierContext>,
+    config: Res<Config>,
+) {
+    let flame_shape = Collider::ball(TORCH_FLAME_RADIUS);
+    for mut torch in &mut torches {
+        if !torch.lit {
+            continue;
+        }
+        let mut hit = false;
+        rapier_context.intersections_with_shape(
+            torch.flame_position,
+            Quat::IDENTITY,
+            &flame_shape,
+            QueryFilter::only_dynamic().exclude_sensors(),
+            |entity| {
+                hit = velocities.get(entity).is_ok_and(|velocity| {
+                    velocity.linvel.length() >= config.torch_extinguish_min_speed
+                });
+                // Stop looking once we've found something fast enough.
+                !hit
+            },
+        );
+        if hit {
+            info!("A torch was knocked out.");
+            torch.set_lit(false);
+        }
+    }
+}
+
+/// Lights the torches that the player uses.
+fn relight_torches(
+    mut interactions: EventReader<Interacted>,
+    torch_interactions: Query<&TorchInteraction>,
+    mut torches: Query<&mut Torch>,
+) {
+    for interaction in interactions.iter() {
+        let Ok(torch_interaction) = torch_interactions.get(interaction.entity) else {
+            continue;
+        };
+        if let Ok(mut torch) = torches.get_mut(torch_interaction.torch) {
+            if !torch.lit {
+                info!("Lit a torch.");
+                torch.set_lit(true);
+            }
+        }
+    }
+}
+
+fn random_particle(
+    kind: ParticleKind,
+    torch: Entity,
+    origin: Vec3,
+    rng: &mut ParticleRng,
+) -> (Particle, Vec3) {
+    let jitter = Vec3::new(rng.range(-1.0, 1.0), 0.0, rng.range(-1.0, 1.0));
+    let (lifetime, velocity, offset) = match kind {
+        ParticleKind::Flame => (
+            rng.range(0.3, 0.6),
+            Vec3::new(0.0, rng.range(0.3, 0.6), 0.0) + jitter * 0.05,
+            jitter * 0.04,
+        ),
+        ParticleKind::Ember => (
+            rng.range(1.0, 2.0),
+            Vec3::new(0.0, rng.range(0.5, 1.0), 0.0) + jitter * 0.2,
+            jitter * 0.05,
+        ),
+        ParticleKind::Smoke => (
+            rng.range(2.0, 3.0),
+            Vec3::new(0.0, rng.range(0.2, 0.35), 0.0) + jitter * 0.05,
+            Vec3::Y * 0.15 + jitter * 0.05,
+        ),
+    };
+    let particle = Particle {
+        kind,
+        torch,
+        origin,
+        age: 0.0,
+        lifetime,
+        velocity,
+    };
+    (particle, origin + offset)
+}
+
+/// The size of a particle, in meters, `t` of the way through its life.
+fn particle_size(kind: ParticleKind, t: f32) -> f32 {
+    match kind {
+        ParticleKind::Flame => 0.12 * (1.0 - t),
+        ParticleKind::Ember => 0.025 * (1.0 - t * t),
+        // Smoke spreads out as it rises, then thins away.
+        ParticleKind::Smoke => 0.4 * t.sqrt() * (1.0 - t * t),
+    }
+}
+
+/// Respawns every torch's particles when torches appear or the particle
+/// settings change.
+fn spawn_torch_particles(
+    mut commands: Commands,
+    new_torches: Query<(), Added<Torch>>,
+    torches: Query<(Entity, &Torch)>,
+    particles: Query<Entity, With<Particle>>,
+    assets: Res<ParticleAssets>,
+    mut rng: ResMut<ParticleRng>,
+    config: Res<Config>,
+) {
+    if new_torches.is_empty() && !config.is_changed() {
+        return;
+    }
+    for entity in &particles {
+        commands.entity(entity).despawn();
+    }
+    if !config.torch_particles_enabled || config.low_quality {
+        return;
+    }
+
+    let kinds = [
+        (
+            ParticleKind::Flame,
+            config.torch_flame_particles,
+            &assets.flame,
+        ),
+        (
+            ParticleKind::Ember,
+            config.torch_ember_particles,
+            &assets.ember,
+        ),
+        (
+            ParticleKind::Smoke,
+            config.torch_smoke_particles,
+            &assets.smoke,
+        ),
+    ];
+    for (entity, torch) in &torches {
+        for (kind, count, material) in kinds {
+            for _ in 0..count {
+                let (mut particle, position) =
+                    random_particle(kind, entity, torch.flame_position, &mut rng);
+                // Start part way through life, so they don't all rise together.
+                particle.age = rng.next() * particle.lifetime;
+                commands.spawn((
+                    PbrBundle {
+                        mesh: assets.mesh.clone(),
+                        material: material.clone(),
+                        transform: Transform::from_translation(
+                            position + particle.velocity * particle.age,
+                        ),
+                        ..default()
+                    },
+                    particle,
+                    NotShadowCaster,
+                    NotShadowReceiver,
+                    Name::new("TorchParticle"),
+                ));
+            }
+        }
+    }
+}
+
+fn update_torch_particles(
+    time: Res<Time>,
+    mut particles: Query<(&mut Particle, &mut Transform, &mut Visibility)>,
+    torches: Query<&Torch>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut rng: ResMut<ParticleRng>,
+) {
+    let dt = time.delta_seconds();
+    // Particles are flat, so we always turn them to face the camera.
+    let camera_rotation = camera_query.get_single().map_or(Quat::IDENTITY, |camera| {
+        camera.to_scale_rotation_translation().1
+    });
+    for (mut particle, mut transform, mut visibility) in &mut particles {
+        let lit = torches
+            .get(particle.torch)
+            .is_ok_and(|torch| torch.is_lit());
+        let new_visibility = if lit {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+        particle.age += dt;
+        if particle.age >= particle.lifetime {
+            let (new_particle, position) =
+                random_particle(particle.kind, particle.torch, particle.origin, &mut rng);
+            *particle = new_particle;
+            transform.translation = position;
+        }
+        transform.translation += particle.velocity * dt;
+        transform.rotation = camera_rotation;
+        transform.scale = Vec3::splat(particle_size(
+            particle.kind,
+            particle.age / particle.lifetime,
+        ));
+    }
+}
+
+fn despawn_torch_entities(
+    mut commands: Commands,
+    entities: Query<Entity, Or<(With<Particle>, With<TorchInteraction>)>>,
+) {
+    for entity in &entities {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Makes torch lights flicker and gives torches flames, embers and smoke.
+/// Torches can be blown out with the force push or by throwing things at them,
+/// and lit again by using them.
+pub struct TorchPlugin;
+
+impl Plugin for TorchPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ParticleRng(0x9e3779b9))
+            .add_systems(Startup, setup_particle_assets)
+            .add_event::<TorchToggled>()
+            .add_systems(OnEnter(AppState::SettingUpScene), setup_torches)
+            .add_systems(OnExit(AppState::InGame), despawn_torch_entities)
+            .add_systems(
+                FixedUpdate,
+                extinguish_torches_hit_by_bodies
+                    .after(PhysicsSet::Writeback)
+                    .run_if(in_state(AppState::InGame)),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        extinguish_torches_with_force_push,
+                        relight_torches,
+                        update_lit_torches,
+                    )
+                        .chain()
+                        .before(update_managed_lights),
+                    flicker_torch_lights.before(update_managed_lights),
+                    spawn_torch_particles,
+                    update_torch_particles.after(spawn_torch_particles),
+                )
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
@@ -0,0 +1,234 @@
+//! A harness for running the game headlessly in tests and driving it with
+//! scripted input, one frame at a time.
+//!
+//! The test level in `assets/tests/test_level.gltf` is a 20x20 meter floor
+//! whose top is at y = 0, centered on the origin, with a 2 meter crate
+//! centered at (7, 1, 0) whose material, `Glow`, is emissive. The player
+//! spawns at the origin facing +X, towards the crate. Its objects are sized by
+//! their vertices rather than by scaling their nodes, since Rapier ignores the
+//! scale of colliders added during scene setup.
+
+#![allow(dead_code)]
+
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bevy::{
+    input::{keyboard::KeyboardInput, mouse::MouseButtonInput, mouse::MouseMotion, ButtonState},
+    log::LogPlugin,
+    prelude::*,
+    time::TimeUpdateStrategy,
+    window::PrimaryWindow,
+};
+use bevy_fun2::{
+    dungeon_scene::DungeonScenePlugin,
+    headless::{headless_default_plugins, start_headless, HEADLESS_TICK_SECONDS},
+    player::Player,
+    save_game::SaveGamePlugin,
+    GamePlugins,
+};
+
+pub const TEST_LEVEL: &str = "tests/test_level";
+
+/// Used to give each test game its own save directory, so that tests running
+/// in parallel don't overwrite each other's saves.
+static GAME_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+pub struct TestGame {
+    pub app: App,
+    /// A temporary directory that the game saves to, which is deleted when the
+    /// game is dropped.
+    save_directory: PathBuf,
+}
+
+impl TestGame {
+    /// Starts the game on the test level and runs it until the player has spawned.
+    pub fn new() -> Self {
+        Self::with(|_| {})
+    }
+
+    /// Like `new`, but lets the app be customized first, e.g. with more plugins.
+    pub fn with(setup: impl FnOnce(&mut App)) -> Self {
+        let save_directory = std::env::temp_dir().join(format!(
+            "bevy-fun2-test-saves-{}-{}",
+            std::process::id(),
+            GAME_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut app = App::new();
+        app.add_plugins(headless_default_plugins().disable::<LogPlugin>())
+            .add_plugins(
+                GamePlugins
+                    .build()
+                    .set(DungeonScenePlugin {
+                        level: TEST_LEVEL.to_string(),
+                    })
+                    .set(SaveGamePlugin {
+                        directory: save_directory.clone(),
+                    }),
+            )
+            .insert_resource(TimeUpdateStrategy::ManualDuration(
+                std::time::Duration::from_secs_f64(HEADLESS_TICK_SECONDS),
+            ));
+        setup(&mut app);
+        start_headless(&mut app).expect("the test game should start");
+        TestGame {
+            app,
+            save_directory,
+        }
+    }
+
+    /// Runs a single frame.
+    pub fn tick(&mut self) {
+        self.app.update();
+    }
+
+    pub fn ticks(&mut self, count: u32) {
+        for _ in 0..count {
+            self.tick();
+        }
+    }
+
+    /// Runs frames for roughly the given number of seconds of game time.
+    pub fn run_for(&mut self, seconds: f32) {
+        self.ticks((seconds as f64 / HEADLESS_TICK_SECONDS).round() as u32);
+    }
+
+    /// Runs frames until the condition holds, for at most the given number of
+    /// seconds of game time. Returns whether the condition was met.
+    pub fn run_until(
+        &mut self,
+        max_seconds: f32,
+        mut condition: impl FnMut(&mut Self) -> bool,
+    ) -> bool {
+        let max_ticks = (max_seconds as f64 / HEADLESS_TICK_SECONDS).round() as u32;
+        for _ in 0..max_ticks {
+            if condition(self) {
+                return true;
+            }
+            self.tick();
+        }
+        condition(self)
+    }
+
+    fn window(&mut self) -> Entity {
+        self.app
+            .world
+            .query_filtered::<Entity, With<PrimaryWindow>>()
+            .single(&self.app.world)
+    }
+
+    fn send_key(&mut self, key: KeyCode, state: ButtonState) {
+        let window = self.window();
+        self.app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key),
+            state,
+            window,
+        });
+    }
+
+    /// The key will be pressed as of the next frame.
+    pub fn press(&mut self, key: KeyCode) {
+        self.send_key(key, ButtonState::Pressed);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.send_key(key, ButtonState::Released);
+    }
+
+    /// Presses a mouse button for a single frame.
+    pub fn click(&mut self, button: MouseButton) {
+        let window = self.window();
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            self.app.world.send_event(MouseButtonInput {
+                button,
+                state,
+                window,
+            });
+            self.tick();
+        }
+    }
+
+    pub fn move_mouse(&mut self, delta: Vec2) {
+        self.app.world.send_event(MouseMotion { delta });
+    }
+
+    pub fn player_entity(&mut self) -> Entity {
+        self.app
+            .world
+            .query_filtered::<Entity, With<Player>>()
+            .single(&self.app.world)
+    }
+
+    pub fn player(&mut self) -> &Player {
+        let entity = self.player_entity();
+        self.app.world.get::<Player>(entity).unwrap()
+    }
+
+    pub fn player_position(&mut self) -> Vec3 {
+        let entity = self.player_entity();
+        self.app.world.get::<Transform>(entity).unwrap().translation
+    }
+
+    pub fn set_player_position(&mut self, position: Vec3) {
+        let entity = self.player_entity();
+        self.app
+            .world
+            .get_mut::<Transform>(entity)
+            .unwrap()
+            .translation = position;
+    }
+
+    /// Moves the player, then runs a couple of frames so that things nearby
+    /// can react, e.g. by being picked up.
+    pub fn teleport_player(&mut self, position: Vec3) {
+        self.set_player_position(position);
+        self.ticks(2);
+    }
+
+    /// The horizontal direction the player's camera is facing.
+    pub fn camera_forward(&mut self) -> Vec3 {
+        let transform = self
+            .app
+            .world
+            .query_filtered::<&Transform, With<Camera3d>>()
+            .single(&self.app.world);
+        let forward = transform.forward();
+        Vec3::new(forward.x, 0.0, forward.z).normalize()
+    }
+
+    /// Spawns an object like those the glTF loader makes for the level's nodes,
+    /// with a child standing in for its mesh. The child has the same name, as it
+    /// would if the mesh's data were named after the object in Blender.
+    pub fn spawn_node(&mut self, name: &str, position: Vec3) -> Entity {
+        self.app
+            .world
+            .spawn((
+                SpatialBundle::from_transform(Transform::from_translation(position)),
+                Name::new(name.to_string()),
+            ))
+            .with_children(|parent| {
+                parent.spawn((SpatialBundle::default(), Name::new(name.to_string())));
+            })
+            .id()
+    }
+
+    pub fn find_by_name(&mut self, name: &str) -> Entity {
+        self.app
+            .world
+            .query::<(Entity, &Name)>()
+            .iter(&self.app.world)
+            .find(|(_, entity_name)| entity_name.as_str() == name)
+            .map(|(entity, _)| entity)
+            .unwrap_or_else(|| panic!("no entity named {}", name))
+    }
+}
+
+impl Drop for TestGame {
+    fn drop(&mut self) {
+        // It won't exist if nothing was saved.
+        let _ = std::fs::remove_dir_all(&self.save_directory);
+    }
+}
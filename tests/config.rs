@@ -0,0 +1,23 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{app_state::AppState, config::Config, console::run_console_command};
+use common::TestGame;
+
+#[test]
+fn set_values_are_kept_when_the_level_is_reloaded() {
+    let mut game = TestGame::new();
+    run_console_command(&mut game.app.world, "set", &["player_speed", "12.5"]).unwrap();
+    assert_eq!(game.app.world.resource::<Config>().player_speed, 12.5);
+
+    game.app
+        .world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::LoadingAssets);
+    game.tick();
+    let reloaded = game.run_until(10.0, |game| {
+        *game.app.world.resource::<State<AppState>>().get() == AppState::InGame
+    });
+    assert!(reloaded);
+    assert_eq!(game.app.world.resource::<Config>().player_speed, 12.5);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{
+    door::Door,
+    inventory::Inventory,
+    save_game::{load_from_slot, save_to_slot},
+};
+use bevy_rapier3d::prelude::*;
+use common::TestGame;
+
+/// Everything written on the screen.
+fn texts(game: &mut TestGame) -> Vec<String> {
+    game.app
+        .world
+        .query::<&Text>()
+        .iter(&game.app.world)
+        .flat_map(|text| text.sections.iter().map(|section| section.value.clone()))
+        .collect()
+}
+
+fn has_text(game: &mut TestGame, needle: &str) -> bool {
+    texts(game).iter().any(|text| text.contains(needle))
+}
+
+#[test]
+fn doors_open_for_players_with_their_key() {
+    let mut game = TestGame::new();
+    let door = game
+        .app
+        .world
+        .spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(3.0, 1.0, 0.0)),
+            Collider::cuboid(0.25, 1.0, 3.0),
+            RigidBody::KinematicPositionBased,
+            Door::new("Gold", 2.0),
+            Name::new("Gold-door"),
+        ))
+        .id();
+    game.spawn_node("Gold-key.001", Vec3::new(0.0, 0.5, 5.0));
+    let door_height =
+        |game: &TestGame| game.app.world.get::<Transform>(door).unwrap().translation.y;
+
+    // Near the end of the door, which is further from its middle than the
+    // unlock distance.
+    game.teleport_player(Vec3::new(1.5, 0.25, 2.5));
+    assert!(has_text(&mut game, "needs the Gold key"));
+    game.run_for(1.0);
+    assert!(!game.app.world.get::<Door>(door).unwrap().is_open());
+
+    game.teleport_player(Vec3::new(0.0, 0.25, 5.0));
+    assert!(game.app.world.resource::<Inventory>().has_key("Gold"));
+    assert!(has_text(&mut game, "Gold key"));
+    assert!(!has_text(&mut game, "needs the Gold key"));
+    save_to_slot(&mut game.app.world, "test").unwrap();
+
+    game.teleport_player(Vec3::new(1.5, 0.25, 0.0));
+    assert!(game.app.world.get::<Door>(door).unwrap().is_open());
+    game.run_for(1.5);
+    assert!((door_height(&game) - 3.0).abs() < 1e-3);
+
+    // The key is kept, but the door is closed again.
+    load_from_slot(&mut game.app.world, "test").unwrap();
+    game.ticks(2);
+    assert!(game.app.world.resource::<Inventory>().has_key("Gold"));
+    assert!(!game.app.world.get::<Door>(door).unwrap().is_open());
+    assert!((door_height(&game) - 1.0).abs() < 1e-3);
+
+    game.app.world.resource_mut::<Inventory>().items.clear();
+    game.tick();
+    assert!(!has_text(&mut game, "Gold key"));
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use bevy::{gltf::Gltf, prelude::*};
+use bevy_fun2::{
+    app_state::AppState,
+    config::{Config, EmissiveOverride},
+};
+use common::{TestGame, TEST_LEVEL};
+
+/// The emissive color of the crate's `Glow` material in the test level.
+const IMPORTED_GLOW: Vec3 = Vec3::new(1.0, 0.5, 0.0);
+
+fn glow_material(game: &mut TestGame) -> Handle<StandardMaterial> {
+    let crate_entity = game.find_by_name("Crate-rigid");
+    let children = game.app.world.get::<Children>(crate_entity).unwrap();
+    children
+        .iter()
+        .find_map(|&child| game.app.world.get::<Handle<StandardMaterial>>(child))
+        .unwrap()
+        .clone()
+}
+
+fn crate_emissive(game: &mut TestGame) -> Vec3 {
+    let material = glow_material(game);
+    let materials = game.app.world.resource::<Assets<StandardMaterial>>();
+    let [r, g, b, _a] = materials
+        .get(&material)
+        .unwrap()
+        .emissive
+        .as_linear_rgba_f32();
+    Vec3::new(r, g, b)
+}
+
+fn set_glow_override(game: &mut TestGame, emissive_override: Option<EmissiveOverride>) {
+    let mut config = game.app.world.resource_mut::<Config>();
+    match emissive_override {
+        Some(emissive_override) => {
+            config
+                .emissive_materials
+                .insert("Glow".to_string(), emissive_override);
+        }
+        None => {
+            config.emissive_materials.remove("Glow");
+        }
+    }
+    game.tick();
+}
+
+#[test]
+fn emissive_overrides_can_be_reapplied() {
+    let mut game = TestGame::new();
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW, 1e-5));
+
+    let scaled = |scale| EmissiveOverride { color: None, scale };
+    set_glow_override(&mut game, Some(scaled(4.0)));
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW * 4.0, 1e-5));
+    // Scales apply to the imported color rather than compounding.
+    set_glow_override(&mut game, Some(scaled(2.0)));
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW * 2.0, 1e-5));
+
+    set_glow_override(
+        &mut game,
+        Some(EmissiveOverride {
+            color: Some(Color::rgb_linear(0.0, 1.0, 0.0)),
+            scale: 3.0,
+        }),
+    );
+    assert!(crate_emissive(&mut game).abs_diff_eq(Vec3::new(0.0, 3.0, 0.0), 1e-5));
+
+    set_glow_override(&mut game, None);
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW, 1e-5));
+}
+
+#[test]
+fn emissive_overrides_apply_to_reloaded_levels() {
+    let mut game = TestGame::new();
+    // Override it in the loaded config, which is applied again on reloading.
+    let mut configs = game.app.world.resource_mut::<Assets<Config>>();
+    for (_, config) in configs.iter_mut() {
+        let scaled = EmissiveOverride {
+            color: None,
+            scale: 4.0,
+        };
+        config.emissive_materials.insert("Glow".to_string(), scaled);
+    }
+    game.tick();
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW * 4.0, 1e-5));
+
+    // Reloading the level doesn't compound the override.
+    game.app
+        .world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::LoadingAssets);
+    game.tick();
+    let reloaded = game.run_until(10.0, |game| {
+        *game.app.world.resource::<State<AppState>>().get() == AppState::InGame
+    });
+    assert!(reloaded);
+    assert!(crate_emissive(&mut game).abs_diff_eq(IMPORTED_GLOW * 4.0, 1e-5));
+
+    // Nor does hot reloading it after it's been re-exported with a new color.
+    let material = glow_material(&mut game);
+    game.app
+        .world
+        .resource_mut::<Assets<StandardMaterial>>()
+        .get_mut(&material)
+        .unwrap()
+        .emissive = Color::rgb_linear(0.0, 0.0, 1.0);
+    let gltf: Handle<Gltf> = game
+        .app
+        .world
+        .resource::<AssetServer>()
+        .get_handle(format!("{}.gltf", TEST_LEVEL));
+    game.app
+        .world
+        .send_event(AssetEvent::Modified { handle: gltf });
+    game.tick();
+    assert!(crate_emissive(&mut game).abs_diff_eq(Vec3::new(0.0, 0.0, 4.0), 1e-5));
+}
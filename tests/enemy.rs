@@ -0,0 +1,110 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{
+    enemy::{spawn_enemy, Enemy, EnemyState},
+    health::Health,
+    player::PlayerForcePush,
+};
+use bevy_rapier3d::prelude::*;
+use common::TestGame;
+
+fn enemy_state(game: &TestGame, enemy: Entity) -> EnemyState {
+    game.app.world.get::<Enemy>(enemy).unwrap().state()
+}
+
+fn health(game: &TestGame, entity: Entity) -> f32 {
+    game.app.world.get::<Health>(entity).unwrap().current
+}
+
+#[test]
+fn enemy_chases_and_attacks_the_player_it_sees() {
+    let mut game = TestGame::new();
+    // Off to the side of the crate, facing the player at the origin.
+    let enemy = spawn_enemy(
+        &mut game.app.world,
+        "Enemy",
+        Vec3::new(0.0, 0.0, 6.0),
+        Quat::IDENTITY,
+        vec![],
+    );
+    game.tick();
+    assert_eq!(enemy_state(&game, enemy), EnemyState::Chase);
+
+    let player = game.player_entity();
+    let start_health = health(&game, player);
+    assert!(game.run_until(5.0, |game| enemy_state(game, enemy) == EnemyState::Attack));
+    game.run_for(1.5);
+    assert!(health(&game, player) < start_health);
+    let player_position = game.app.world.get::<Transform>(player).unwrap().translation;
+    let enemy_position = game.app.world.get::<Transform>(enemy).unwrap().translation;
+    assert!(player_position.distance(enemy_position) < 2.0);
+}
+
+#[test]
+fn enemy_patrols_waypoints_when_it_cant_see_the_player() {
+    let mut game = TestGame::new();
+    // Facing and walking away from the player, so it can't see them.
+    let waypoints = vec![Vec3::new(-4.0, 0.0, 10.0), Vec3::new(-4.0, 0.0, 8.0)];
+    let enemy = spawn_enemy(
+        &mut game.app.world,
+        "Enemy",
+        Vec3::new(-4.0, 0.0, 8.0),
+        Quat::from_rotation_y(std::f32::consts::PI),
+        waypoints,
+    );
+    assert!(game.run_until(5.0, |game| enemy_state(game, enemy) == EnemyState::Patrol));
+    assert!(game.run_until(5.0, |game| {
+        let position = game.app.world.get::<Transform>(enemy).unwrap().translation;
+        Vec2::new(position.x, position.z).distance(Vec2::new(-4.0, 10.0)) < 0.5
+    }));
+    assert_ne!(enemy_state(&game, enemy), EnemyState::Chase);
+}
+
+#[test]
+fn force_pushed_bodies_hurt_enemies_they_hit() {
+    let mut game = TestGame::new();
+    // Between the player and the crate, facing away from the player so that it
+    // doesn't move.
+    let enemy = spawn_enemy(
+        &mut game.app.world,
+        "Enemy",
+        Vec3::new(4.0, 0.0, 0.0),
+        Quat::from_rotation_y(std::f32::consts::PI),
+        vec![],
+    );
+    game.tick();
+    let crate_entity = game.find_by_name("Crate-rigid");
+    game.app
+        .world
+        .get_mut::<Velocity>(crate_entity)
+        .unwrap()
+        .linvel = Vec3::new(-10.0, 0.0, 0.0);
+    game.app.world.send_event(PlayerForcePush {
+        target: Some(crate_entity),
+    });
+    let start_health = health(&game, enemy);
+    assert!(game.run_until(1.0, |game| health(game, enemy) < start_health));
+
+    // Something hitting slower than `enemy_impact_min_speed` doesn't hurt.
+    let mut game = TestGame::new();
+    let enemy = spawn_enemy(
+        &mut game.app.world,
+        "Enemy",
+        Vec3::new(5.5, 0.0, 0.0),
+        Quat::from_rotation_y(std::f32::consts::PI),
+        vec![],
+    );
+    let crate_entity = game.find_by_name("Crate-rigid");
+    game.app
+        .world
+        .get_mut::<Velocity>(crate_entity)
+        .unwrap()
+        .linvel = Vec3::new(-1.8, 0.0, 0.0);
+    game.app.world.send_event(PlayerForcePush {
+        target: Some(crate_entity),
+    });
+    let start_health = health(&game, enemy);
+    game.run_for(1.0);
+    assert_eq!(health(&game, enemy), start_health);
+}
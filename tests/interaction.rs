@@ -0,0 +1,112 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{
+    config::{Config, KeyBinding},
+    interaction::{Interactable, Interacted, InteractionTarget},
+};
+use bevy_rapier3d::prelude::*;
+use common::TestGame;
+
+/// The entities the player has used so far.
+#[derive(Resource, Default)]
+struct Used(Vec<Entity>);
+
+fn record_interactions(mut events: EventReader<Interacted>, mut used: ResMut<Used>) {
+    used.0.extend(events.iter().map(|event| event.entity));
+}
+
+fn spawn_lever(game: &mut TestGame, position: Vec3) -> Entity {
+    game.app
+        .world
+        .spawn((
+            TransformBundle::from_transform(Transform::from_translation(position)),
+            Collider::ball(0.3),
+            Sensor,
+            Interactable::new("Pull lever"),
+        ))
+        .id()
+}
+
+fn target(game: &TestGame) -> Option<Entity> {
+    game.app.world.resource::<InteractionTarget>().0
+}
+
+fn prompt(game: &mut TestGame) -> String {
+    let entity = game.find_by_name("InteractionPrompt");
+    let children = game.app.world.get::<Children>(entity).unwrap();
+    let text = game.app.world.get::<Text>(children[0]).unwrap();
+    text.sections[0].value.clone()
+}
+
+fn eye_height(game: &mut TestGame) -> f32 {
+    game.app.world.resource::<Config>().player_camera_height + game.player_position().y
+}
+
+fn press_and_release(game: &mut TestGame, key: KeyCode) {
+    game.press(key);
+    game.tick();
+    game.release(key);
+    game.tick();
+}
+
+#[test]
+fn players_can_use_what_they_look_at() {
+    let mut game = TestGame::with(|app| {
+        app.init_resource::<Used>()
+            .add_systems(Update, record_interactions);
+    });
+    // The player looks along +X from about 1.25 meters up.
+    let eye_height = eye_height(&mut game);
+    let lever = spawn_lever(&mut game, Vec3::new(2.0, eye_height, 0.0));
+    // Off to the side for now.
+    let distant_lever = spawn_lever(&mut game, Vec3::new(2.0, eye_height, 5.0));
+    game.ticks(2);
+    assert_eq!(target(&game), Some(lever));
+    assert_eq!(prompt(&mut game), "E: Pull lever");
+
+    press_and_release(&mut game, KeyCode::E);
+    assert_eq!(game.app.world.resource::<Used>().0, vec![lever]);
+
+    game.app
+        .world
+        .get_mut::<Interactable>(lever)
+        .unwrap()
+        .enabled = false;
+    game.tick();
+    assert_eq!(target(&game), None);
+    assert_eq!(prompt(&mut game), "");
+
+    // Too far away to use, even when it's straight ahead.
+    game.app
+        .world
+        .get_mut::<Transform>(distant_lever)
+        .unwrap()
+        .translation = Vec3::new(5.0, eye_height, 0.0);
+    game.app.world.despawn(lever);
+    game.ticks(2);
+    assert_eq!(target(&game), None);
+}
+
+#[test]
+fn the_use_key_can_be_rebound() {
+    let mut game = TestGame::with(|app| {
+        app.init_resource::<Used>()
+            .add_systems(Update, record_interactions);
+    });
+    let instructions = game.find_by_name("Instructions");
+    let text = game.app.world.get::<Text>(instructions).unwrap();
+    assert!(text.sections[0].value.contains("Press E to light a torch."));
+
+    game.app.world.resource_mut::<Config>().use_key = KeyBinding(KeyCode::Key1);
+    let eye_height = eye_height(&mut game);
+    let lever = spawn_lever(&mut game, Vec3::new(2.0, eye_height, 0.0));
+    game.ticks(2);
+    assert_eq!(prompt(&mut game), "1: Pull lever");
+
+    press_and_release(&mut game, KeyCode::E);
+    assert!(game.app.world.resource::<Used>().0.is_empty());
+
+    press_and_release(&mut game, KeyCode::Key1);
+    assert_eq!(game.app.world.resource::<Used>().0, vec![lever]);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::config::Config;
+use common::TestGame;
+
+fn spawn_light(game: &mut TestGame, position: Vec3, range: f32) -> Entity {
+    game.app
+        .world
+        .spawn(PointLightBundle {
+            point_light: PointLight {
+                intensity: 800.0,
+                range,
+                ..default()
+            },
+            transform: Transform::from_translation(position),
+            ..default()
+        })
+        .id()
+}
+
+#[test]
+fn only_the_nearest_lights_in_view_cast_shadows() {
+    let mut game = TestGame::new();
+    {
+        let mut config = game.app.world.resource_mut::<Config>();
+        config.low_quality = false;
+        config.max_shadowed_lights = 2;
+        config.light_fade_start_distance = 20.0;
+        config.light_fade_end_distance = 30.0;
+    }
+    // The player faces +X.
+    let near = spawn_light(&mut game, Vec3::new(3.0, 2.0, 0.0), 2.0);
+    let middle = spawn_light(&mut game, Vec3::new(6.0, 2.0, 0.0), 2.0);
+    let behind = spawn_light(&mut game, Vec3::new(-2.0, 2.0, 0.0), 1.0);
+    let further = spawn_light(&mut game, Vec3::new(9.0, 2.0, 0.0), 2.0);
+    let fading = spawn_light(&mut game, Vec3::new(25.0, 2.0, 0.0), 2.0);
+    let distant = spawn_light(&mut game, Vec3::new(50.0, 2.0, 0.0), 2.0);
+    game.ticks(3);
+
+    let world = &game.app.world;
+    let shadows = |light| world.get::<PointLight>(light).unwrap().shadows_enabled;
+    let intensity = |light| world.get::<PointLight>(light).unwrap().intensity;
+    let visibility = |light| *world.get::<Visibility>(light).unwrap();
+    assert!(shadows(near));
+    assert!(shadows(middle));
+    // The light behind the player is closer, but can't light anything they
+    // can see.
+    assert!(!shadows(behind));
+    assert!(!shadows(further));
+    assert_eq!(intensity(near), 800.0);
+    assert!(intensity(fading) > 0.0 && intensity(fading) < 800.0);
+    assert_eq!(visibility(distant), Visibility::Hidden);
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{
+    config::Config,
+    navmesh::{NavMesh, NavMeshSettings},
+};
+use bevy_rapier3d::prelude::*;
+use common::TestGame;
+
+/// Distance from a point to a box centered on the origin, ignoring height.
+fn distance_to_wall(point: Vec3, half_extents: Vec2) -> f32 {
+    let outside = (Vec2::new(point.x, point.z).abs() - half_extents).max(Vec2::ZERO);
+    outside.length()
+}
+
+#[test]
+fn paths_go_around_walls() {
+    let mut game = TestGame::new();
+    assert!(game.app.world.get_resource::<NavMesh>().is_some());
+
+    // A wall across the way from the player to the crate, which is made into
+    // part of the navmesh by throwing away the current one.
+    let half_extents = Vec2::new(0.25, 3.0);
+    game.app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(3.0, 1.0, 0.0)),
+        RigidBody::Fixed,
+        Collider::cuboid(half_extents.x, 1.0, half_extents.y),
+    ));
+    game.app.world.remove_resource::<NavMesh>();
+    assert!(game.run_until(1.0, |game| game
+        .app
+        .world
+        .get_resource::<NavMesh>()
+        .is_some()));
+
+    let navmesh = game.app.world.resource::<NavMesh>();
+    let path = navmesh
+        .find_path(Vec3::ZERO, Vec3::new(5.0, 0.0, 0.0))
+        .expect("there should be a way around the wall");
+    assert!(path.first().unwrap().distance(Vec3::ZERO) < 0.2);
+    assert!(path.last().unwrap().distance(Vec3::new(5.0, 0.0, 0.0)) < 0.2);
+    // Every leg of the path keeps the player's capsule clear of the wall.
+    for leg in path.windows(2) {
+        for step in 0..=10 {
+            let point = leg[0].lerp(leg[1], step as f32 / 10.0) - Vec3::new(3.0, 0.0, 0.0);
+            assert!(distance_to_wall(point, half_extents) > 0.2, "{:?}", path);
+        }
+    }
+
+    // Nowhere near the floor.
+    assert!(navmesh
+        .find_path(Vec3::ZERO, Vec3::new(30.0, 0.0, 0.0))
+        .is_none());
+}
+
+#[test]
+fn navmeshes_can_be_baked() {
+    let mut game = TestGame::new();
+    let navmesh = game.app.world.resource::<NavMesh>();
+    let json = serde_json::to_string(navmesh).unwrap();
+    let baked: NavMesh = serde_json::from_str(&json).unwrap();
+    assert_eq!(baked.settings, navmesh.settings);
+    assert_eq!(baked.node_count(), navmesh.node_count());
+    let end = Vec3::new(-5.0, 0.0, 5.0);
+    assert_eq!(
+        baked.find_path(Vec3::ZERO, end),
+        navmesh.find_path(Vec3::ZERO, end)
+    );
+    let settings = NavMeshSettings::from_config(game.app.world.resource::<Config>());
+    assert!(baked.is_up_to_date(game.app.world.resource::<RapierContext>(), settings));
+
+    // Once the level has changed, the baked navmesh is out of date.
+    game.app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(3.0, 1.0, 0.0)),
+        RigidBody::Fixed,
+        Collider::cuboid(0.25, 1.0, 3.0),
+    ));
+    game.ticks(2);
+    assert!(!baked.is_up_to_date(game.app.world.resource::<RapierContext>(), settings));
+}
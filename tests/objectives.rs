@@ -0,0 +1,98 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{
+    collectible::{Collectible, CollectibleKind},
+    config::Config,
+    objectives::{Exit, LevelProgress, Objective, PressurePlate},
+    save_game::{load_from_slot, save_to_slot},
+};
+use bevy_rapier3d::prelude::*;
+use common::{TestGame, TEST_LEVEL};
+
+fn is_collected(game: &TestGame, entity: Entity) -> bool {
+    game.app
+        .world
+        .get::<Collectible>(entity)
+        .unwrap()
+        .is_collected()
+}
+
+fn progress(game: &TestGame) -> &LevelProgress {
+    game.app.world.resource::<LevelProgress>()
+}
+
+#[test]
+fn completing_objectives_completes_the_level() {
+    let mut game = TestGame::new();
+    game.app.world.resource_mut::<Config>().objectives.insert(
+        TEST_LEVEL.to_string(),
+        vec![
+            Objective::Collect {
+                kind: CollectibleKind::Coin,
+                count: None,
+            },
+            Objective::PressPlates,
+            Objective::ReachExit,
+        ],
+    );
+    let coins = [Vec3::new(0.0, 0.5, 4.0), Vec3::new(0.0, 0.5, -4.0)]
+        .map(|position| game.spawn_node(&format!("{}-coin", position.z), position));
+    // Under the crate.
+    game.app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(7.0, 0.0, 0.0)),
+        Collider::cuboid(1.0, 0.1, 1.0),
+        Sensor,
+        PressurePlate,
+    ));
+    game.app.world.spawn((
+        TransformBundle::from_transform(Transform::from_xyz(-5.0, 1.0, 0.0)),
+        Collider::cuboid(1.0, 1.0, 1.0),
+        Sensor,
+        Exit,
+    ));
+    game.ticks(2);
+    let counts = |game: &TestGame| {
+        progress(game)
+            .objectives
+            .iter()
+            .map(|objective| (objective.done, objective.total))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(counts(&game), vec![(0, 2), (1, 1), (0, 1)]);
+
+    // The exit doesn't count until everything else is done.
+    game.teleport_player(Vec3::new(-5.0, 0.25, 0.0));
+    assert!(!progress(&game).exit_reached);
+
+    game.teleport_player(Vec3::new(0.0, 0.25, 4.0));
+    assert!(is_collected(&game, coins[0]));
+    assert_eq!(
+        game.app.world.get::<Visibility>(coins[0]),
+        Some(&Visibility::Hidden)
+    );
+    save_to_slot(&mut game.app.world, "test").unwrap();
+
+    game.teleport_player(Vec3::new(0.0, 0.25, -4.0));
+    assert!(is_collected(&game, coins[1]));
+    game.teleport_player(Vec3::new(-5.0, 0.25, 0.0));
+    assert!(progress(&game).exit_reached);
+    assert!(progress(&game).completed);
+    game.find_by_name("LevelCompleteScreen");
+    // The timer stops once the level is complete.
+    let elapsed = progress(&game).elapsed_seconds;
+    game.run_for(0.5);
+    assert_eq!(progress(&game).elapsed_seconds, elapsed);
+
+    // Loading a save from before then puts back the second coin.
+    load_from_slot(&mut game.app.world, "test").unwrap();
+    game.ticks(2);
+    assert!(is_collected(&game, coins[0]));
+    assert!(!is_collected(&game, coins[1]));
+    assert_eq!(
+        game.app.world.get::<Visibility>(coins[1]),
+        Some(&Visibility::Inherited)
+    );
+    assert!(!progress(&game).completed);
+    assert!(progress(&game).elapsed_seconds < elapsed);
+}
@@ -0,0 +1,272 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{prelude::*, time::TimeUpdateStrategy};
+use bevy_fun2::{
+    config::Config,
+    health::{Dying, Health},
+    player::Player,
+};
+use bevy_rapier3d::prelude::*;
+use common::TestGame;
+
+/// How close positions need to be to count as the same, in meters.
+const TOLERANCE: f32 = 0.05;
+
+fn settle(game: &mut TestGame) {
+    game.run_for(0.5);
+}
+
+#[test]
+fn player_lands_on_floor() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    assert!(game.player().is_grounded());
+    let radius = game.app.world.resource::<Config>().player_capsule_radius;
+    let position = game.player_position();
+    // Our origin is at the center of the bottom hemisphere of our capsule, so
+    // it's a radius away from the floor, give or take the controller's offset.
+    assert!(
+        (position.y - radius).abs() < TOLERANCE,
+        "player is at {}",
+        position
+    );
+    assert!(
+        Vec2::new(position.x, position.z).length() < TOLERANCE,
+        "player is at {}",
+        position
+    );
+}
+
+#[test]
+fn walking_moves_player_forward() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    let start = game.player_position();
+    let speed = game.app.world.resource::<Config>().player_speed;
+
+    game.press(KeyCode::W);
+    game.run_for(1.0);
+    game.release(KeyCode::W);
+    game.tick();
+
+    let moved = game.player_position() - start;
+    assert!(game.player().is_grounded());
+    assert!(
+        (moved.x - speed).abs() < speed * 0.1,
+        "player moved {}",
+        moved
+    );
+    assert!(moved.z.abs() < TOLERANCE, "player moved {}", moved);
+    assert!(moved.y.abs() < TOLERANCE, "player moved {}", moved);
+}
+
+#[test]
+fn jumping_leaves_and_returns_to_ground() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    let start = game.player_position();
+
+    game.press(KeyCode::Space);
+    game.ticks(10);
+    game.release(KeyCode::Space);
+    assert!(!game.player().is_grounded());
+    assert!(game.player_position().y > start.y + 0.3);
+
+    game.run_for(1.5);
+    assert!(game.player().is_grounded());
+    assert!((game.player_position().y - start.y).abs() < TOLERANCE);
+}
+
+/// The highest the player has been at the end of a fixed timestep.
+#[derive(Resource)]
+struct HighestPlayerY(f32);
+
+fn record_highest_player_y(
+    mut highest: ResMut<HighestPlayerY>,
+    query: Query<&Transform, With<Player>>,
+) {
+    for transform in &query {
+        highest.0 = highest.0.max(transform.translation.y);
+    }
+}
+
+/// How high the player jumps when the game runs at the given frame rate.
+fn jump_apex_at_frame_rate(frames_per_second: f64) -> f32 {
+    let mut game = TestGame::with(|app| {
+        app.insert_resource(HighestPlayerY(f32::MIN)).add_systems(
+            FixedUpdate,
+            record_highest_player_y.after(PhysicsSet::Writeback),
+        );
+    });
+    game.app
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / frames_per_second,
+        )));
+    let ticks_per_second = frames_per_second.round() as u32;
+    game.ticks(ticks_per_second / 2);
+    assert!(game.player().is_grounded());
+    let start = game.player_position().y;
+    game.app.insert_resource(HighestPlayerY(start));
+
+    game.press(KeyCode::Space);
+    game.tick();
+    game.release(KeyCode::Space);
+    game.ticks(2 * ticks_per_second);
+    assert!(game.player().is_grounded());
+    game.app.world.resource::<HighestPlayerY>().0 - start
+}
+
+#[test]
+fn jump_apex_is_the_same_at_any_frame_rate() {
+    let apex = jump_apex_at_frame_rate(60.0);
+    assert!(apex > 0.5, "player only jumped {} meters", apex);
+    for frames_per_second in [24.0, 75.0, 144.0, 240.0] {
+        let other_apex = jump_apex_at_frame_rate(frames_per_second);
+        assert!(
+            (other_apex - apex).abs() < 1e-4,
+            "player jumped {} meters at {} FPS, but {} meters at 60 FPS",
+            other_apex,
+            frames_per_second,
+            apex
+        );
+    }
+}
+
+#[test]
+fn mouse_look_changes_walking_direction() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    let initial_forward = game.camera_forward();
+
+    game.move_mouse(Vec2::new(-500.0, 0.0));
+    game.tick();
+    let forward = game.camera_forward();
+    assert!(
+        forward.dot(initial_forward) < 0.9,
+        "camera still faces {}",
+        forward
+    );
+
+    let start = game.player_position();
+    game.press(KeyCode::W);
+    game.run_for(0.5);
+    game.release(KeyCode::W);
+    let moved = (game.player_position() - start).normalize();
+    assert!(
+        moved.dot(forward) > 0.99,
+        "player moved towards {} instead of {}",
+        moved,
+        forward
+    );
+}
+
+#[test]
+fn falling_off_level_respawns_player() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    let spawn = game.player_position();
+
+    // Beyond the edge of the floor.
+    game.set_player_position(Vec3::new(20.0, 1.0, 0.0));
+    let player = game.player_entity();
+    let died = game.run_until(10.0, |game| game.app.world.get::<Dying>(player).is_some());
+    assert!(
+        died,
+        "player didn't die, and is at {}",
+        game.player_position()
+    );
+
+    let death_fade_duration = game.app.world.resource::<Config>().death_fade_duration;
+    game.run_for(death_fade_duration);
+    settle(&mut game);
+    assert!(game.app.world.get::<Dying>(player).is_none());
+    let health = game.app.world.get::<Health>(player).unwrap();
+    assert_eq!(health.current, health.max);
+    assert!(game.player().is_grounded());
+    assert!(
+        game.player_position().distance(spawn) < TOLERANCE,
+        "player respawned at {} instead of {}",
+        game.player_position(),
+        spawn
+    );
+}
+
+#[test]
+fn force_push_pulls_crate_towards_player() {
+    let mut game = TestGame::new();
+    settle(&mut game);
+    let crate_entity = game.find_by_name("Crate-rigid");
+    let start = game
+        .app
+        .world
+        .get::<Transform>(crate_entity)
+        .unwrap()
+        .translation;
+
+    game.click(MouseButton::Right);
+    game.ticks(5);
+
+    let velocity = game.app.world.get::<Velocity>(crate_entity).unwrap().linvel;
+    assert!(velocity.x < -0.5, "crate is moving at {}", velocity);
+    game.run_for(1.0);
+    let position = game
+        .app
+        .world
+        .get::<Transform>(crate_entity)
+        .unwrap()
+        .translation;
+    assert!(
+        position.x < start.x - 0.5,
+        "crate moved from {} to {}",
+        start,
+        position
+    );
+}
+
+/// Walks into a box of the given mass, returning how fast it was moving once
+/// it started to.
+fn speed_of_pushed_box(mass: f32) -> f32 {
+    let mut game = TestGame::new();
+    let box_entity = game
+        .app
+        .world
+        .spawn((
+            TransformBundle::from_transform(Transform::from_xyz(1.5, 0.5, 0.0)),
+            RigidBody::Dynamic,
+            Collider::cuboid(0.5, 0.5, 0.5),
+            ColliderMassProperties::Mass(mass),
+            Velocity::default(),
+            ReadMassProperties::default(),
+        ))
+        .id();
+    settle(&mut game);
+
+    game.press(KeyCode::W);
+    let velocity = |game: &TestGame| game.app.world.get::<Velocity>(box_entity).unwrap().linvel;
+    let moved = game.run_until(2.0, |game| velocity(game).x > 0.01);
+    assert!(moved, "the {} kg box wasn't pushed", mass);
+    // Nothing is pushed faster than the player walks.
+    let speed = game.app.world.resource::<Config>().player_speed;
+    let box_speed = velocity(&game).x;
+    assert!(
+        box_speed <= speed + TOLERANCE,
+        "the {} kg box moved at {}",
+        mass,
+        box_speed
+    );
+    box_speed
+}
+
+#[test]
+fn heavier_bodies_are_harder_to_push() {
+    let light = speed_of_pushed_box(2.0);
+    let heavy = speed_of_pushed_box(15.0);
+    assert!(
+        light > heavy * 2.0,
+        "light box moved at {} and heavy box at {}",
+        light,
+        heavy
+    );
+}
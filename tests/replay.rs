@@ -0,0 +1,141 @@
+mod common;
+
+use std::time::Duration;
+
+use bevy::{app::AppExit, prelude::*, time::TimeUpdateStrategy};
+use bevy_fun2::{
+    replay::{world_state_hash, InputReplay, RecordPlugin, RecordedTick, Recording, ReplayPlugin},
+    time_scale::FIXED_TIMESTEP_SECONDS,
+};
+use common::TestGame;
+
+fn recording_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "bevy-fun2-replay-{}-{}.json",
+        name,
+        std::process::id()
+    ))
+}
+
+/// Records the session that `play` plays, returning the recording and the
+/// game it was recorded in.
+fn record(name: &str, play: impl FnOnce(&mut TestGame)) -> (Recording, TestGame) {
+    let path = recording_path(name);
+    let mut game = TestGame::with(|app| {
+        app.add_plugins(RecordPlugin { path: path.clone() });
+    });
+    play(&mut game);
+    game.app.world.send_event(AppExit);
+    game.tick();
+    let recording = Recording::load(path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    (recording, game)
+}
+
+fn replay(recording: &Recording) -> TestGame {
+    let mut replay = TestGame::with(|app| {
+        app.add_plugins(ReplayPlugin {
+            recording: recording.clone(),
+            exit_when_finished: false,
+        });
+    });
+    let finished = replay.run_until(10.0, |replay| {
+        replay.app.world.resource::<InputReplay>().is_finished()
+    });
+    assert!(finished);
+    replay
+}
+
+/// Plays a little of everything: walking, looking around, jumping and pulling
+/// the crate.
+fn play(game: &mut TestGame) {
+    game.run_for(0.5);
+    game.press(KeyCode::W);
+    game.run_for(0.5);
+    game.move_mouse(Vec2::new(-200.0, 50.0));
+    game.press(KeyCode::Space);
+    game.tick();
+    game.release(KeyCode::Space);
+    game.run_for(0.5);
+    game.release(KeyCode::W);
+    game.move_mouse(Vec2::new(200.0, -50.0));
+    game.tick();
+    game.click(MouseButton::Right);
+    game.run_for(1.0);
+}
+
+#[test]
+fn replay_reproduces_recorded_session() {
+    let (recording, mut game) = record("session", play);
+    let live_hash = world_state_hash(&mut game.app.world);
+    assert!(recording.ticks.iter().any(|tick| !tick.keys.is_empty()));
+    assert_eq!(recording.final_state_hash, Some(live_hash));
+
+    // Replaying more than once makes sure nothing from one run leaks into the next.
+    for _ in 0..2 {
+        let replay = replay(&recording);
+        assert_eq!(
+            replay
+                .app
+                .world
+                .resource::<InputReplay>()
+                .final_state_hash(),
+            Some(live_hash)
+        );
+    }
+}
+
+#[test]
+fn input_is_recorded_per_fixed_timestep() {
+    // Two fixed timesteps pass every frame while recording, but the replay
+    // runs one a frame.
+    let (recording, mut game) = record("frame-rate", |game| {
+        game.app
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                FIXED_TIMESTEP_SECONDS * 2.0,
+            )));
+        game.ticks(10);
+        game.press(KeyCode::W);
+        game.ticks(15);
+        game.move_mouse(Vec2::new(-200.0, 50.0));
+        game.press(KeyCode::Space);
+        game.tick();
+        game.release(KeyCode::Space);
+        game.ticks(15);
+        game.release(KeyCode::W);
+        game.move_mouse(Vec2::new(200.0, -50.0));
+        game.tick();
+        game.click(MouseButton::Right);
+        game.ticks(15);
+    });
+    // The frame the game started on had a single timestep, and each of the 60
+    // after it had two.
+    assert_eq!(recording.ticks.len(), 1 + 2 * 60);
+    let first_tick_with =
+        |has_input: &dyn Fn(&RecordedTick) -> bool| recording.ticks.iter().position(has_input);
+    // The input of each frame goes to the first timestep after it.
+    assert_eq!(
+        first_tick_with(&|tick| tick.keys.iter().any(|&(key, _)| key == KeyCode::W)),
+        Some(1 + 2 * 10)
+    );
+    assert_eq!(
+        first_tick_with(&|tick| !tick.mouse_motion.is_empty()),
+        Some(1 + 2 * 25)
+    );
+    assert_eq!(
+        first_tick_with(&|tick| !tick.buttons.is_empty()),
+        Some(1 + 2 * 42)
+    );
+
+    let live_hash = world_state_hash(&mut game.app.world);
+    assert_eq!(recording.final_state_hash, Some(live_hash));
+    let replay = replay(&recording);
+    assert_eq!(
+        replay
+            .app
+            .world
+            .resource::<InputReplay>()
+            .final_state_hash(),
+        Some(live_hash)
+    );
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::save_game::{load_from_slot, save_to_slot};
+use common::TestGame;
+
+#[test]
+fn loading_restores_player_camera_and_bodies() {
+    let mut game = TestGame::new();
+    game.run_for(0.5);
+    game.move_mouse(Vec2::new(-200.0, 100.0));
+    game.tick();
+    let crate_entity = game.find_by_name("Crate-rigid");
+    let crate_position = |game: &TestGame| {
+        game.app
+            .world
+            .get::<Transform>(crate_entity)
+            .unwrap()
+            .translation
+    };
+    let player_position = game.player_position();
+    let camera_forward = game.camera_forward();
+    let crate_start = crate_position(&game);
+    save_to_slot(&mut game.app.world, "test").unwrap();
+
+    // Walk away, look elsewhere and pull the crate.
+    game.move_mouse(Vec2::new(300.0, -100.0));
+    game.press(KeyCode::W);
+    game.run_for(1.0);
+    game.release(KeyCode::W);
+    game.click(MouseButton::Right);
+    game.run_for(1.0);
+    assert!(game.player_position().distance(player_position) > 1.0);
+    assert!(crate_position(&game).distance(crate_start) > 0.5);
+
+    load_from_slot(&mut game.app.world, "test").unwrap();
+    game.tick();
+    assert!(
+        game.player_position().distance(player_position) < 0.05,
+        "player is at {} instead of {}",
+        game.player_position(),
+        player_position
+    );
+    assert!(
+        game.camera_forward().dot(camera_forward) > 0.999,
+        "camera faces {} instead of {}",
+        game.camera_forward(),
+        camera_forward
+    );
+    assert!(
+        crate_position(&game).distance(crate_start) < 0.05,
+        "crate is at {} instead of {}",
+        crate_position(&game),
+        crate_start
+    );
+}
+
+fn press_and_release(game: &mut TestGame, key: KeyCode) {
+    game.press(key);
+    game.tick();
+    game.release(key);
+    game.tick();
+}
+
+fn save_message(game: &mut TestGame) -> String {
+    let entity = game.find_by_name("SaveMessage");
+    let children = game.app.world.get::<Children>(entity).unwrap();
+    let text = game.app.world.get::<Text>(children[0]).unwrap();
+    text.sections[0].value.clone()
+}
+
+#[test]
+fn players_can_pick_which_slot_to_save_to() {
+    let mut game = TestGame::new();
+    game.run_for(0.5);
+    let player_position = game.player_position();
+
+    press_and_release(&mut game, KeyCode::F6);
+    assert!(save_message(&mut game).starts_with("Slot 1 selected."));
+    press_and_release(&mut game, KeyCode::F5);
+    assert_eq!(save_message(&mut game), "Saved to slot 'slot1'.");
+    // Nothing went in the quicksave slot.
+    assert!(load_from_slot(&mut game.app.world, "quick").is_err());
+
+    game.teleport_player(Vec3::new(3.0, 0.25, 3.0));
+    press_and_release(&mut game, KeyCode::F9);
+    game.tick();
+    assert!(
+        game.player_position().distance(player_position) < 0.05,
+        "player is at {} instead of {}",
+        game.player_position(),
+        player_position
+    );
+
+    // The message goes away after a while.
+    game.run_for(4.0);
+    assert_eq!(save_message(&mut game), "");
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use bevy::prelude::*;
+use bevy_fun2::{console::run_console_command, time_scale::SimulationTime};
+use common::TestGame;
+
+/// How many times `FixedUpdate` has run.
+#[derive(Resource, Default)]
+struct FixedSteps(u32);
+
+fn count_fixed_steps(mut steps: ResMut<FixedSteps>) {
+    steps.0 += 1;
+}
+
+fn frozen_game() -> TestGame {
+    let mut game = TestGame::with(|app| {
+        app.init_resource::<FixedSteps>()
+            .add_systems(FixedUpdate, count_fixed_steps);
+    });
+    game.run_for(0.5);
+    // Get the player and the crate moving, so freezing has something to stop.
+    game.click(MouseButton::Right);
+    game.press(KeyCode::W);
+    game.ticks(2);
+    run_console_command(&mut game.app.world, "physics", &["freeze"]).unwrap();
+    game.tick();
+    game
+}
+
+fn transforms(game: &mut TestGame) -> (Transform, Transform) {
+    let player = game.player_entity();
+    let crate_entity = game.find_by_name("Crate-rigid");
+    let world = &game.app.world;
+    (
+        *world.get::<Transform>(player).unwrap(),
+        *world.get::<Transform>(crate_entity).unwrap(),
+    )
+}
+
+#[test]
+fn freezing_stops_the_simulation() {
+    let mut game = frozen_game();
+    let steps = game.app.world.resource::<FixedSteps>().0;
+    let before = transforms(&mut game);
+
+    game.ticks(10);
+
+    assert_eq!(game.app.world.resource::<FixedSteps>().0, steps);
+    assert_eq!(transforms(&mut game), before);
+}
+
+#[test]
+fn physics_step_advances_exactly_one_step() {
+    let mut game = frozen_game();
+    let steps = game.app.world.resource::<FixedSteps>().0;
+    let before = transforms(&mut game);
+
+    run_console_command(&mut game.app.world, "physics", &["step"]).unwrap();
+    game.tick();
+
+    assert_eq!(game.app.world.resource::<FixedSteps>().0, steps + 1);
+    let after = transforms(&mut game);
+    assert_ne!(after.0, before.0, "the player didn't move");
+    assert_ne!(after.1, before.1, "the crate didn't move");
+    assert!(game.app.world.resource::<SimulationTime>().frozen);
+
+    game.ticks(10);
+    assert_eq!(game.app.world.resource::<FixedSteps>().0, steps + 1);
+    assert_eq!(transforms(&mut game), after);
+}